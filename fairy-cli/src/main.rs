@@ -1,11 +1,18 @@
-use std::{collections::HashMap, net::SocketAddr, path::PathBuf, sync::Arc};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use dale_http::prelude::*;
+use anyhow::{bail, Context};
 use dale_runtime::Tokio;
+use fairy_core::{
+    load_env, ConfigFile, ImportHint, Mode, OsFileSystem, Resolver, TargetEnv, DEFAULT_ENV_PREFIX,
+};
 use fairy_http::{create_routes, Options, RenderRequest};
 use hyper::Server;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
@@ -14,8 +21,65 @@ struct Args {
     #[arg(short, long)]
     root: PathBuf,
 
+    /// Defaults to `address` of the config, or 127.0.0.1:3000
     #[arg(short, long)]
-    address: SocketAddr,
+    address: Option<SocketAddr>,
+
+    /// Config file, instead of fairy.toml or fairy.json in root
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Section of the config file to apply. There is no build command
+    /// yet, so `build` is only accepted by `explain`
+    #[arg(short, long, default_value = "dev", value_parser = parse_mode)]
+    mode: Mode,
+
+    /// Warn about lockfile mismatches, duplicates and unmet peer
+    /// dependencies in node_modules on start
+    #[arg(long)]
+    check_install: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Show how a specifier is resolved
+    Explain {
+        specifier: String,
+        /// File importing the specifier, relative to root
+        #[arg(short, long, default_value = "main.js")]
+        importer: String,
+        /// Resolve for node instead of the browser
+        #[arg(long)]
+        node: bool,
+        /// Resolve as `require` instead of `import`
+        #[arg(long)]
+        require: bool,
+    },
+}
+
+fn parse_mode(mode: &str) -> Result<Mode, String> {
+    match mode {
+        "dev" => Ok(Mode::Dev),
+        "build" => Ok(Mode::Build),
+        _ => Err(format!("unknown mode '{}', expected dev or build", mode)),
+    }
+}
+
+fn load_config(args: &Args, root: &Path) -> anyhow::Result<ConfigFile> {
+    let path = match &args.config {
+        Some(path) => path.clone(),
+        None => match ConfigFile::find(&OsFileSystem, root) {
+            Some(path) => path,
+            None => return Ok(ConfigFile::default()),
+        },
+    };
+
+    log::debug!("using config {:?} ({})", path, args.mode);
+
+    Ok(ConfigFile::load(&OsFileSystem, &path, args.mode)?)
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -24,28 +88,76 @@ async fn main() -> anyhow::Result<()> {
 
     let args = Args::parse();
 
-    // let addr = ([127, 0, 0, 1], 3000).into();
+    let root = args
+        .root
+        .canonicalize()
+        .with_context(|| format!("invalid root {:?}", args.root))?;
+
+    let file = load_config(&args, &root)?;
+    let mut config = file.config;
 
-    // let options = Options::build("fa")
+    if let Some(Command::Explain {
+        specifier,
+        importer,
+        node,
+        require,
+    }) = args.command
+    {
+        let resolver = Resolver::with_options(root, config.resolve);
 
-    // let mut env = HashMap::default();
-    // env.insert("NODE_ENV".into(), "development".into());
+        let hint = if require {
+            ImportHint::Require
+        } else {
+            ImportHint::Import
+        };
+
+        let target = if node {
+            TargetEnv::Node
+        } else {
+            TargetEnv::Browser
+        };
+
+        println!("{}", resolver.explain(&importer, &specifier, hint, target));
+
+        return Ok(());
+    }
+
+    if args.mode == Mode::Build {
+        bail!("the dev server only runs in dev mode, `--mode build` is for `explain`");
+    }
+
+    if config.entry.as_str().is_empty() {
+        bail!("no entry module, set `entry` in fairy.toml");
+    }
+
+    let address = args
+        .address
+        .or(file.address)
+        .unwrap_or_else(|| ([127, 0, 0, 1], 3000).into());
+
+    // Explicit variables of the config win over .env files
+    let prefix = file.env_prefix.as_deref().unwrap_or(DEFAULT_ENV_PREFIX);
+    let mut env = load_env(&OsFileSystem, &root, args.mode, prefix)?;
+    env.extend(config.env);
+    config.env = env;
+
+    config.check_install |= args.check_install;
+
+    let mut options = Options::build(root)
+        .config(config)
+        .template(|req| dale_http::Result::Ok(Home { cfg: &req }.to_string().into_bytes()));
+
+    if let Some(public) = file.public {
+        options = options.public(public);
+    }
 
-    // let cfg = Options {
-    //     root: PathBuf::from("./fairy-http/examples/frontend").canonicalize()?,
-    //     entry: RelativePathBuf::from("/src/main.tsx"),
-    //     env,
-    //     template: Box::new(|req| {
-    //         //
-    //         Home { cfg: &req }.to_string().into_bytes()
-    //     }),
-    // };
+    let service = create_routes::<_, Tokio>(Arc::new(options.build()?));
 
-    // let service = create_routes::<_, Tokio>(Arc::new(cfg));
+    log::info!("listening on http://{}", address);
 
-    // Server::bind(&addr)
-    //     .serve(dale_http::hyper::make(service))
-    //     .await;
+    Server::bind(&address)
+        .serve(dale_http::hyper::make(service))
+        .await?;
 
     Ok(())
 }
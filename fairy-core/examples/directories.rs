@@ -0,0 +1,29 @@
+use std::path::PathBuf;
+
+use fairy_core::{ImportHint, Resolver, TargetEnv};
+
+fn main() -> anyhow::Result<()> {
+    pretty_env_logger::init();
+
+    let resolver = Resolver::new(PathBuf::from("fairy-core/fixtures/directories").canonicalize()?);
+
+    // Directory index, nested package.json main, main pointing at a
+    // directory and a subpath directory of a package
+    for id in [
+        "./components",
+        "./lib",
+        "dir-main",
+        "dir-main/utils",
+        "nested-main",
+    ] {
+        let package =
+            resolver.resolve("src/main.js", id, ImportHint::Import, TargetEnv::Browser)?;
+        println!(
+            "{} => {:?}",
+            id,
+            package.root.join(package.entry.path.as_str())
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use std::{path::PathBuf, sync::Arc};
+
+use fairy_core::{ImportHint, MemoryFileSystem, ResolveOptions, Resolver, TargetEnv};
+
+fn main() -> anyhow::Result<()> {
+    pretty_env_logger::init();
+
+    // A project that only exists in memory
+    let fs = MemoryFileSystem::new()
+        .with_file("/app/package.json", r#"{ "name": "app" }"#)
+        .with_file("/app/src/main.ts", "import 'lib';")
+        .with_file("/app/src/utils/index.ts", "export {};")
+        .with_file(
+            "/app/node_modules/lib/package.json",
+            r#"{ "name": "lib", "exports": { ".": { "import": "./esm/index.js" } } }"#,
+        )
+        .with_file("/app/node_modules/lib/esm/index.js", "export {};");
+
+    let resolver = Resolver::with_file_system(
+        PathBuf::from("/app"),
+        ResolveOptions::default(),
+        Arc::new(fs),
+    );
+
+    for id in ["./utils", "lib"] {
+        let package =
+            resolver.resolve("src/main.ts", id, ImportHint::Import, TargetEnv::Browser)?;
+        println!(
+            "{} => {:?}",
+            id,
+            package.root.join(package.entry.path.as_str())
+        );
+    }
+
+    Ok(())
+}
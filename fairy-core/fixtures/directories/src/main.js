@@ -0,0 +1,5 @@
+import { Button } from "./components";
+import { lib } from "./lib";
+import dirMain from "dir-main";
+import utils from "dir-main/utils";
+import nestedMain from "nested-main";
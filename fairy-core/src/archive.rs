@@ -0,0 +1,164 @@
+use crate::{FileSystem, FileType};
+use parking_lot::{Mutex, RwLock};
+use std::{
+    collections::HashMap,
+    io::{self, Cursor, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use zip::{result::ZipError, ZipArchive};
+
+type Archive = Arc<Mutex<ZipArchive<Cursor<Vec<u8>>>>>;
+
+/// Files inside zip archives (eg. the yarn cache) can be read as if the
+/// archive was a directory. Archives are read through `inner`
+#[derive(Debug)]
+pub struct ArchiveFileSystem {
+    inner: Arc<dyn FileSystem>,
+    /// Opened archives. Yarn names cached packages by checksum, so they
+    /// don't change once written
+    archives: RwLock<HashMap<PathBuf, Archive>>,
+}
+
+impl ArchiveFileSystem {
+    pub fn new(inner: Arc<dyn FileSystem>) -> ArchiveFileSystem {
+        ArchiveFileSystem {
+            inner,
+            archives: Default::default(),
+        }
+    }
+
+    /// Split a path into the zip archive containing it and the path inside
+    /// the archive
+    fn split_archive(&self, path: &Path) -> Option<(PathBuf, String)> {
+        let mut archive = PathBuf::new();
+        let mut components = path.components();
+
+        while let Some(component) = components.next() {
+            archive.push(component);
+            if archive.extension().map(|ext| ext == "zip").unwrap_or(false)
+                && (self.archives.read().contains_key(&archive) || self.inner.is_file(&archive))
+            {
+                let inner = components.as_path().to_string_lossy().replace('\\', "/");
+                return Some((archive, inner));
+            }
+        }
+
+        None
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Archive> {
+        if let Some(found) = self.archives.read().get(path) {
+            return Ok(found.clone());
+        }
+
+        let archive = ZipArchive::new(Cursor::new(self.inner.read(path)?)).map_err(zip_error)?;
+        let archive = Arc::new(Mutex::new(archive));
+
+        self.archives
+            .write()
+            .insert(path.to_path_buf(), archive.clone());
+
+        Ok(archive)
+    }
+}
+
+fn zip_error(err: ZipError) -> io::Error {
+    match err {
+        ZipError::Io(err) => err,
+        ZipError::FileNotFound => io::Error::new(io::ErrorKind::NotFound, err),
+        err => io::Error::new(io::ErrorKind::InvalidData, err),
+    }
+}
+
+impl FileSystem for ArchiveFileSystem {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let (archive, inner) = match self.split_archive(path) {
+            Some(ret) => ret,
+            None => return self.inner.read(path),
+        };
+
+        let archive = self.open(&archive)?;
+        let mut archive = archive.lock();
+        let mut file = archive.by_name(&inner).map_err(zip_error)?;
+
+        let mut data = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut data)?;
+
+        Ok(data)
+    }
+
+    fn file_type(&self, path: &Path) -> io::Result<FileType> {
+        let (archive, inner) = match self.split_archive(path) {
+            Some(ret) => ret,
+            None => return self.inner.file_type(path),
+        };
+
+        if inner.is_empty() {
+            return Ok(FileType::Dir);
+        }
+
+        let archive = self.open(&archive)?;
+        let archive = archive.lock();
+
+        // Directories don't necessarily have entries of their own
+        let dir = format!("{}/", inner.trim_end_matches('/'));
+        let mut found = None;
+
+        for name in archive.file_names() {
+            if name == inner {
+                return Ok(FileType::File);
+            }
+            if name.starts_with(&dir) {
+                found = Some(FileType::Dir);
+            }
+        }
+
+        found.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} not found", inner)))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.inner.read_dir(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        // Archives hold no links
+        match self.split_archive(path) {
+            Some(_) => Ok(path.to_path_buf()),
+            None => self.inner.canonicalize(path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryFileSystem;
+    use std::io::Write;
+    use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+    fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::default()));
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, content) in files {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn reads_archives_through_inner() {
+        let inner = MemoryFileSystem::new().with_file(
+            "/cache/lib.zip",
+            zip(&[("node_modules/lib/package.json", r#"{ "name": "lib" }"#)]),
+        );
+        let fs = ArchiveFileSystem::new(Arc::new(inner));
+
+        let package = Path::new("/cache/lib.zip/node_modules/lib/package.json");
+        assert!(fs.is_file(package));
+        assert!(fs.is_dir(Path::new("/cache/lib.zip/node_modules/lib")));
+        assert!(!fs.exists(Path::new("/cache/lib.zip/node_modules/other")));
+        assert_eq!(fs.read_to_string(package).unwrap(), r#"{ "name": "lib" }"#);
+    }
+}
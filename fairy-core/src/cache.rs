@@ -0,0 +1,295 @@
+use crate::{
+    lockfile::{Lockfile, PACKAGE_LOCK_JSON, PNPM_LOCK_YAML, YARN_LOCK},
+    package::PACKAGE_JSON,
+    pnp::{PnpManifest, PNP_CJS, PNP_DATA_JSON},
+    trace::{self, TraceStep},
+    tsconfig::{TsConfig, TSCONFIG_JSON},
+    FileSystem, ImportHint, Package, PackageJson, TargetEnv, Workspace, NODE_MODULES,
+};
+use parking_lot::RwLock;
+use relative_path::RelativePathBuf;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+pub(crate) type ResolveKey = (RelativePathBuf, String, ImportHint, TargetEnv);
+
+/// Filesystem lookups made while resolving. Only found files and links are
+/// kept, so files created later are picked up without `invalidate`
+#[derive(Debug)]
+pub(crate) struct ResolverCache {
+    fs: Arc<dyn FileSystem>,
+    exists: RwLock<HashSet<PathBuf>>,
+    files: RwLock<HashSet<PathBuf>>,
+    real_paths: RwLock<HashMap<PathBuf, PathBuf>>,
+    package_json: RwLock<HashMap<PathBuf, PackageJson>>,
+    tsconfig: RwLock<HashMap<PathBuf, Option<Arc<TsConfig>>>>,
+    pnp: RwLock<HashMap<PathBuf, Option<Arc<PnpManifest>>>>,
+    workspace: RwLock<Option<Option<Arc<Workspace>>>>,
+    lockfile: RwLock<Option<Option<Arc<Lockfile>>>>,
+    resolved: RwLock<HashMap<ResolveKey, Package>>,
+}
+
+impl ResolverCache {
+    pub fn new(fs: Arc<dyn FileSystem>) -> ResolverCache {
+        ResolverCache {
+            fs,
+            exists: Default::default(),
+            files: Default::default(),
+            real_paths: Default::default(),
+            package_json: Default::default(),
+            tsconfig: Default::default(),
+            pnp: Default::default(),
+            workspace: Default::default(),
+            lockfile: Default::default(),
+            resolved: Default::default(),
+        }
+    }
+
+    pub fn fs(&self) -> &Arc<dyn FileSystem> {
+        &self.fs
+    }
+
+    pub fn exists(&self, path: &Path) -> bool {
+        if self.exists.read().contains(path) {
+            return true;
+        }
+
+        let exists = self.fs.exists(path);
+        if exists {
+            self.exists.write().insert(path.to_path_buf());
+        }
+        exists
+    }
+
+    pub fn is_file(&self, path: &Path) -> bool {
+        if self.files.read().contains(path) {
+            return true;
+        }
+
+        let is_file = self.fs.is_file(path);
+        if is_file {
+            self.files.write().insert(path.to_path_buf());
+        }
+        is_file
+    }
+
+    /// The canonical path, or `path` itself when it cannot be resolved
+    pub fn real_path(&self, path: &Path) -> PathBuf {
+        if let Some(found) = self.real_paths.read().get(path) {
+            return found.clone();
+        }
+
+        match self.fs.canonicalize(path) {
+            Ok(real) => {
+                self.real_paths
+                    .write()
+                    .insert(path.to_path_buf(), real.clone());
+                real
+            }
+            Err(_) => path.to_path_buf(),
+        }
+    }
+
+    pub fn package_json(&self, root: &Path) -> anyhow::Result<PackageJson> {
+        if let Some(found) = self.package_json.read().get(root) {
+            return Ok(found.clone());
+        }
+
+        let pkgjson = PackageJson::load(&*self.fs, root)?;
+
+        self.package_json
+            .write()
+            .insert(root.to_path_buf(), pkgjson.clone());
+
+        Ok(pkgjson)
+    }
+
+    pub fn find_package_root(&self, path: &Path) -> Option<PathBuf> {
+        let mut parent = path.parent();
+        while let Some(p) = parent {
+            if self.is_file(&p.join(PACKAGE_JSON)) {
+                return Some(p.to_path_buf());
+            }
+            parent = p.parent();
+        }
+        None
+    }
+
+    pub fn find_nearest_package_json(&self, path: &Path) -> Option<(PathBuf, PackageJson)> {
+        let root = self.find_package_root(path)?;
+        self.package_json(&root).map(|pkg| (root, pkg)).ok()
+    }
+
+    pub fn tsconfig(&self, path: &Path) -> Option<Arc<TsConfig>> {
+        if let Some(found) = self.tsconfig.read().get(path) {
+            return found.clone();
+        }
+
+        let tsconfig = match TsConfig::load(&*self.fs, path) {
+            Ok(ret) => Some(Arc::new(ret)),
+            Err(err) => {
+                log::error!("could not load tsconfig: {:?}", err);
+                None
+            }
+        };
+
+        self.tsconfig
+            .write()
+            .insert(path.to_path_buf(), tsconfig.clone());
+
+        tsconfig
+    }
+
+    pub fn find_tsconfig(&self, path: &Path) -> Option<Arc<TsConfig>> {
+        let mut parent = path.parent();
+        while let Some(p) = parent {
+            let tsconfig = p.join(TSCONFIG_JSON);
+            if self.is_file(&tsconfig) {
+                return self.tsconfig(&tsconfig);
+            }
+            parent = p.parent();
+        }
+        None
+    }
+
+    pub fn pnp(&self, path: &Path) -> Option<Arc<PnpManifest>> {
+        if let Some(found) = self.pnp.read().get(path) {
+            return found.clone();
+        }
+
+        let manifest = match PnpManifest::load(&*self.fs, path) {
+            Ok(ret) => Some(Arc::new(ret)),
+            Err(err) => {
+                log::error!("could not load pnp manifest: {:?}", err);
+                None
+            }
+        };
+
+        self.pnp
+            .write()
+            .insert(path.to_path_buf(), manifest.clone());
+
+        manifest
+    }
+
+    pub fn find_pnp(&self, path: &Path) -> Option<Arc<PnpManifest>> {
+        let mut parent = path.parent();
+        while let Some(p) = parent {
+            for name in [PNP_DATA_JSON, PNP_CJS] {
+                let manifest = p.join(name);
+                if self.is_file(&manifest) {
+                    return self.pnp(&manifest);
+                }
+            }
+            parent = p.parent();
+        }
+        None
+    }
+
+    pub fn workspace(&self, root: &Path) -> Option<Arc<Workspace>> {
+        if let Some(found) = &*self.workspace.read() {
+            return found.clone();
+        }
+
+        let workspace = Workspace::find(&*self.fs, root).map(Arc::new);
+        *self.workspace.write() = Some(workspace.clone());
+        workspace
+    }
+
+    pub fn lockfile(&self, root: &Path) -> Option<Arc<Lockfile>> {
+        if let Some(found) = &*self.lockfile.read() {
+            return found.clone();
+        }
+
+        let lockfile = Lockfile::find(&*self.fs, root).map(Arc::new);
+        *self.lockfile.write() = Some(lockfile.clone());
+        lockfile
+    }
+
+    pub fn find_nearest_external(&self, path: &Path, id: &str) -> Option<PathBuf> {
+        let mut parent = Some(path);
+
+        while let Some(path) = parent {
+            let node_modules_path = path.join(NODE_MODULES);
+            let found = self.exists(&node_modules_path) && self.exists(&node_modules_path.join(id));
+
+            trace::record(|| TraceStep::NodeModules {
+                path: node_modules_path.clone(),
+                found,
+            });
+
+            if found {
+                return Some(node_modules_path);
+            }
+
+            parent = path.parent();
+        }
+
+        None
+    }
+
+    pub fn resolved(&self, key: &ResolveKey) -> Option<Package> {
+        self.resolved.read().get(key).cloned()
+    }
+
+    pub fn insert_resolved(&self, key: ResolveKey, package: Package) {
+        self.resolved.write().insert(key, package);
+    }
+
+    /// Forget everything known about `path` and anything below it.
+    /// Resolved specifiers are dropped entirely, since a new or removed
+    /// file can change the outcome of any lookup.
+    pub fn invalidate(&self, path: &Path) {
+        self.exists.write().retain(|key| !key.starts_with(path));
+        self.files.write().retain(|key| !key.starts_with(path));
+        // Links may have been replaced anywhere below a removed directory
+        self.real_paths
+            .write()
+            .retain(|key, real| !key.starts_with(path) && !real.starts_with(path));
+        self.package_json
+            .write()
+            .retain(|key, _| !key.join(PACKAGE_JSON).starts_with(path));
+        // Packages may have been added to or removed from the workspace
+        if path.ends_with(PACKAGE_JSON) || path.extension().is_none() {
+            *self.workspace.write() = None;
+        }
+        let is_lockfile = path
+            .file_name()
+            .map(|name| {
+                [YARN_LOCK, PACKAGE_LOCK_JSON, PNPM_LOCK_YAML]
+                    .iter()
+                    .any(|n| name == *n)
+            })
+            .unwrap_or(false);
+        if is_lockfile || path.extension().is_none() {
+            *self.lockfile.write() = None;
+        }
+        // Any json file could be part of an `extends` chain
+        if path.extension().map(|ext| ext == "json").unwrap_or(true) {
+            self.tsconfig.write().clear();
+        }
+        let is_pnp = path
+            .file_name()
+            .map(|name| name.to_string_lossy().starts_with(".pnp"))
+            .unwrap_or(false);
+        if is_pnp || path.extension().is_none() {
+            self.pnp.write().clear();
+        }
+        self.resolved.write().clear();
+    }
+
+    pub fn clear(&self) {
+        self.exists.write().clear();
+        self.files.write().clear();
+        self.real_paths.write().clear();
+        self.package_json.write().clear();
+        self.tsconfig.write().clear();
+        self.pnp.write().clear();
+        *self.workspace.write() = None;
+        *self.lockfile.write() = None;
+        self.resolved.write().clear();
+    }
+}
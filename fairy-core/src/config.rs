@@ -1,19 +1,167 @@
-use core::fmt;
+use crate::{FileLoader, ImportHint, TargetEnv, EXTENSIONS};
 use relative_path::RelativePathBuf;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 pub type Environ = HashMap<String, String>;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Config {
+    /// Never read from config files, the root is where they are found
+    #[serde(skip)]
     pub root: PathBuf,
+    /// Entry module, relative to the project root
+    #[serde(default)]
     pub entry: RelativePathBuf,
+    /// Variables exposed to client code, in addition to those from
+    /// `.env` files
     #[serde(default)]
     pub env: Environ,
+    /// Loaders for imports of non-script files, eg. `{ type = "css" }`
+    #[serde(default)]
+    pub plugins: Vec<Arc<dyn FileLoader>>,
+    #[serde(default)]
+    pub resolve: ResolveOptions,
+    /// Warn about lockfile mismatches, duplicates and unmet peer
+    /// dependencies in node_modules on start
+    #[serde(default)]
+    pub check_install: bool,
+}
+
+/// Resolution settings for a single target. Empty lists use the defaults
+/// of the target.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetOptions {
+    /// Export conditions for `import`, in order of preference
+    #[serde(default)]
+    pub import: Vec<String>,
+    /// Export conditions for `require`, in order of preference
+    #[serde(default)]
+    pub require: Vec<String>,
+    /// package.json fields used as entry when a package has no `exports`
+    #[serde(default)]
+    pub main_fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BuiltinFallback {
+    /// Fail to resolve
+    #[default]
+    Error,
+    /// Resolve to an empty module
+    Empty,
+}
+
+/// How node built-in modules (`fs`, `node:path`, ...) are resolved for
+/// browser targets. Node targets always leave them external.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuiltinOptions {
+    /// Used for built-ins without a polyfill
+    #[serde(default)]
+    pub fallback: BuiltinFallback,
+    /// Built-in => package (or path relative to the project root)
+    /// replacing it, eg. `path` => `path-browserify`
+    #[serde(default)]
+    pub polyfills: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResolveOptions {
+    /// Custom conditions (eg. `development` or `react-server`), preferred
+    /// over the conditions of the target
     #[serde(default)]
-    pub plugins: Vec<Box<dyn FileLoader>>,
+    pub conditions: Vec<String>,
+    #[serde(default)]
+    pub browser: TargetOptions,
+    #[serde(default)]
+    pub node: TargetOptions,
+    /// Specifiers to replace before resolution, eg. `react` => `preact/compat`.
+    /// Keys also match as prefix (`~` matches `~/components`). Targets
+    /// starting with `./` are relative to the project root.
+    #[serde(default)]
+    pub alias: HashMap<String, String>,
+    #[serde(default)]
+    pub builtins: BuiltinOptions,
+    /// Keep symlinked packages at their link location instead of resolving
+    /// their dependencies from the real path (like node's --preserve-symlinks)
+    #[serde(default)]
+    pub preserve_symlinks: bool,
+    /// Packages that must only be loaded once (eg. `react`). They are
+    /// always resolved from the project root, whoever imports them
+    #[serde(default)]
+    pub dedupe: Vec<String>,
+    /// Extensions tried, in order, for files imported without one. Empty
+    /// uses `EXTENSIONS`
+    #[serde(default)]
+    pub extensions: Vec<String>,
 }
 
-#[typetag::serde]
-pub trait FileLoader: fmt::Debug {}
+impl ResolveOptions {
+    pub fn target(&self, target: TargetEnv) -> &TargetOptions {
+        match target {
+            TargetEnv::Browser => &self.browser,
+            TargetEnv::Node => &self.node,
+        }
+    }
+
+    pub fn conditions(&self, target: TargetEnv, hint: ImportHint) -> Vec<&str> {
+        let configured = match hint {
+            ImportHint::Import => &self.target(target).import,
+            ImportHint::Require => &self.target(target).require,
+        };
+
+        let mut conditions = self
+            .conditions
+            .iter()
+            .map(|m| m.as_str())
+            .collect::<Vec<_>>();
+
+        if configured.is_empty() {
+            conditions.extend(target.export_fields(hint));
+        } else {
+            conditions.extend(configured.iter().map(|m| m.as_str()));
+        }
+
+        conditions
+    }
+
+    pub fn alias(&self, id: &str) -> Option<String> {
+        if let Some(found) = self.alias.get(id) {
+            return Some(found.clone());
+        }
+
+        self.alias
+            .iter()
+            .filter(|(key, _)| {
+                id.starts_with(key.as_str())
+                    && (key.ends_with('/') || id[key.len()..].starts_with('/'))
+            })
+            .max_by_key(|(key, _)| key.len())
+            .map(|(key, target)| format!("{}{}", target, &id[key.len()..]))
+    }
+
+    pub fn main_fields(&self, target: TargetEnv) -> Vec<&str> {
+        let configured = &self.target(target).main_fields;
+
+        if configured.is_empty() {
+            target.main_fields().to_vec()
+        } else {
+            configured.iter().map(|m| m.as_str()).collect()
+        }
+    }
+
+    pub fn extensions(&self) -> Vec<&str> {
+        if self.extensions.is_empty() {
+            EXTENSIONS.to_vec()
+        } else {
+            self.extensions
+                .iter()
+                .map(|m| m.trim_start_matches('.'))
+                .collect()
+        }
+    }
+}
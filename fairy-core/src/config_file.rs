@@ -0,0 +1,283 @@
+use crate::{Config, ConfigError, FileSystem};
+use relative_path::RelativePathBuf;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+pub static FAIRY_TOML: &str = "fairy.toml";
+
+pub static FAIRY_JSON: &str = "fairy.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Dev,
+    Build,
+}
+
+impl Mode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mode::Dev => "dev",
+            Mode::Build => "build",
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Project settings from `fairy.toml` or `fairy.json`. Settings in a
+/// `[dev]` or `[build]` section override the shared ones in that mode
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfigFile {
+    #[serde(flatten)]
+    pub config: Config,
+    /// Directory of static files, relative to the project root
+    pub public: Option<RelativePathBuf>,
+    /// Address of the dev server
+    pub address: Option<SocketAddr>,
+    /// Prefix of the variables from `.env` files and the process exposed
+    /// to client code. Defaults to `DEFAULT_ENV_PREFIX`
+    pub env_prefix: Option<String>,
+}
+
+impl ConfigFile {
+    /// The config file in `root`, if any. `fairy.toml` is preferred
+    pub fn find(fs: &dyn FileSystem, root: &Path) -> Option<PathBuf> {
+        [FAIRY_TOML, FAIRY_JSON]
+            .iter()
+            .map(|name| root.join(name))
+            .find(|path| fs.is_file(path))
+    }
+
+    pub fn load(fs: &dyn FileSystem, path: &Path, mode: Mode) -> Result<ConfigFile, ConfigError> {
+        let content = fs.read_to_string(path).map_err(|error| ConfigError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        let value: Value = if path.extension().map(|ext| ext == "toml").unwrap_or(false) {
+            toml::from_str(&content).map_err(|err| ConfigError::Syntax {
+                path: path.to_path_buf(),
+                message: err.to_string(),
+            })?
+        } else {
+            serde_json::from_str(&content).map_err(|err| ConfigError::Syntax {
+                path: path.to_path_buf(),
+                message: err.to_string(),
+            })?
+        };
+
+        let mut shared = match value {
+            Value::Object(map) => map,
+            _ => {
+                return Err(ConfigError::Invalid {
+                    path: path.to_path_buf(),
+                    key: String::from("."),
+                    message: String::from("expected a table"),
+                })
+            }
+        };
+
+        let sections = [Mode::Dev, Mode::Build]
+            .iter()
+            .map(|mode| (*mode, shared.remove(mode.as_str())))
+            .collect::<Vec<_>>();
+
+        // Validate every section, not only the one in use, so errors
+        // point at the key as written
+        let shared = Value::Object(shared);
+        validate(path, "", &shared)?;
+
+        let mut merged = shared;
+
+        for (section_mode, section) in sections {
+            let section = match section {
+                Some(section) => section,
+                None => continue,
+            };
+
+            validate(path, section_mode.as_str(), &section)?;
+
+            if section_mode == mode {
+                merge(&mut merged, section);
+            }
+        }
+
+        validate(path, "", &merged)
+    }
+}
+
+fn validate(path: &Path, prefix: &str, value: &Value) -> Result<ConfigFile, ConfigError> {
+    // Flattening `Config` rules out `deny_unknown_fields`, so the known
+    // keys are those of a serialized default
+    let known = match serde_json::to_value(ConfigFile::default()) {
+        Ok(Value::Object(known)) => known,
+        _ => unreachable!("config file serializes to a table"),
+    };
+
+    if let Value::Object(map) = value {
+        if let Some(key) = map.keys().find(|key| !known.contains_key(key.as_str())) {
+            return Err(ConfigError::Invalid {
+                path: path.to_path_buf(),
+                key: match prefix {
+                    "" => key.clone(),
+                    prefix => format!("{}.{}", prefix, key),
+                },
+                message: String::from("unknown field"),
+            });
+        }
+    }
+
+    // Errors inside a flattened struct lose their path, so `Config` is
+    // checked on its own first
+    deserialize::<Config>(path, prefix, value)?;
+    deserialize(path, prefix, value)
+}
+
+fn deserialize<T: DeserializeOwned>(
+    path: &Path,
+    prefix: &str,
+    value: &Value,
+) -> Result<T, ConfigError> {
+    serde_path_to_error::deserialize(value.clone()).map_err(|err| {
+        let key = match (prefix, err.path().to_string()) {
+            ("", key) => key,
+            (prefix, key) if key == "." => prefix.to_string(),
+            (prefix, key) => format!("{}.{}", prefix, key),
+        };
+
+        ConfigError::Invalid {
+            path: path.to_path_buf(),
+            key,
+            message: err.into_inner().to_string(),
+        }
+    })
+}
+
+/// Tables are merged key by key, anything else is replaced
+fn merge(target: &mut Value, value: Value) {
+    match (target, value) {
+        (Value::Object(target), Value::Object(value)) => {
+            for (key, value) in value {
+                match target.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, value) => *target = value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BuiltinFallback, MemoryFileSystem};
+
+    fn load(name: &str, content: &str, mode: Mode) -> Result<ConfigFile, ConfigError> {
+        let path = Path::new("/app").join(name);
+        let fs = MemoryFileSystem::new().with_file(&path, content);
+        ConfigFile::load(&fs, &path, mode)
+    }
+
+    fn invalid_key(err: ConfigError) -> String {
+        match err {
+            ConfigError::Invalid { key, .. } => key,
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+
+    static TOML: &str = r#"
+entry = "./src/main.tsx"
+env_prefix = "APP_"
+plugins = [{ type = "css" }]
+
+[env]
+API = "https://example.com"
+
+[resolve.alias]
+react = "preact/compat"
+
+[dev]
+address = "127.0.0.1:4000"
+
+[dev.env]
+API = "http://localhost:8080"
+
+[build]
+public = "./static"
+
+[build.resolve.builtins]
+fallback = "empty"
+"#;
+
+    #[test]
+    fn merges_mode_sections() {
+        let dev = load(FAIRY_TOML, TOML, Mode::Dev).unwrap();
+        assert_eq!(dev.config.entry, "./src/main.tsx");
+        assert_eq!(dev.config.env["API"], "http://localhost:8080");
+        assert_eq!(dev.config.resolve.alias["react"], "preact/compat");
+        assert_eq!(dev.config.plugins.len(), 1);
+        assert_eq!(dev.address, Some(([127, 0, 0, 1], 4000).into()));
+        assert_eq!(dev.env_prefix.as_deref(), Some("APP_"));
+        assert_eq!(dev.public, None);
+
+        let build = load(FAIRY_TOML, TOML, Mode::Build).unwrap();
+        assert_eq!(build.config.env["API"], "https://example.com");
+        // Tables merge key by key
+        assert_eq!(build.config.resolve.alias["react"], "preact/compat");
+        assert_eq!(
+            build.config.resolve.builtins.fallback,
+            BuiltinFallback::Empty
+        );
+        assert_eq!(build.address, None);
+        assert_eq!(build.public.as_ref().map(|p| p.as_str()), Some("./static"));
+    }
+
+    #[test]
+    fn json() {
+        let config = load(
+            FAIRY_JSON,
+            r#"{ "entry": "main.js", "build": { "entry": "prod.js" } }"#,
+            Mode::Build,
+        )
+        .unwrap();
+
+        assert_eq!(config.config.entry, "prod.js");
+    }
+
+    #[test]
+    fn invalid_keys() {
+        let err = load(FAIRY_TOML, "entri = \"main.js\"", Mode::Dev).unwrap_err();
+        assert_eq!(invalid_key(err), "entri");
+
+        let err = load(FAIRY_TOML, "root = \"/other\"", Mode::Dev).unwrap_err();
+        assert_eq!(invalid_key(err), "root");
+
+        // Sections that aren't in use are checked too
+        let err = load(FAIRY_TOML, "[build]\nadress = \"x\"", Mode::Dev).unwrap_err();
+        assert_eq!(invalid_key(err), "build.adress");
+
+        let err = load(FAIRY_TOML, "[resolve]\nalias = 1", Mode::Dev).unwrap_err();
+        assert_eq!(invalid_key(err), "resolve.alias");
+
+        let err = load(FAIRY_TOML, "[dev.resolve]\nconditon = []", Mode::Dev).unwrap_err();
+        assert_eq!(invalid_key(err), "dev.resolve.conditon");
+
+        assert!(matches!(
+            load(FAIRY_TOML, "entry = ", Mode::Dev).unwrap_err(),
+            ConfigError::Syntax { .. }
+        ));
+    }
+}
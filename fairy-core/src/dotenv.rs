@@ -0,0 +1,318 @@
+use crate::{EnvError, Environ, FileSystem, Mode};
+use std::{collections::HashMap, path::Path};
+
+/// Variables without this prefix are kept out of client code
+pub static DEFAULT_ENV_PREFIX: &str = "FAIRY_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Quoting {
+    /// Single quoted, or from the process: taken literally
+    Literal,
+    /// Unquoted: references are expanded
+    Plain,
+    /// Double quoted: escapes are decoded as well
+    Double,
+}
+
+struct RawValue {
+    value: String,
+    quoting: Quoting,
+}
+
+/// `.env` files in order of increasing precedence
+pub fn env_files(mode: Mode) -> Vec<String> {
+    vec![
+        String::from(".env"),
+        String::from(".env.local"),
+        format!(".env.{}", mode),
+        format!(".env.{}.local", mode),
+    ]
+}
+
+/// Load the `.env` files of `dir` for `mode`. Variables of the process
+/// take precedence over the files. Only variables starting with `prefix`
+/// are returned, with `${VAR}` references expanded
+pub fn load_env(
+    fs: &dyn FileSystem,
+    dir: &Path,
+    mode: Mode,
+    prefix: &str,
+) -> Result<Environ, EnvError> {
+    let mut raw = HashMap::default();
+
+    for name in env_files(mode) {
+        let path = dir.join(&name);
+        if !fs.is_file(&path) {
+            continue;
+        }
+
+        log::debug!("loading env file {:?}", path);
+
+        let content = fs.read_to_string(&path).map_err(|err| EnvError {
+            path: path.clone(),
+            line: 0,
+            message: err.to_string(),
+        })?;
+
+        parse(&path, &content, &mut raw)?;
+    }
+
+    for (key, value) in std::env::vars() {
+        raw.insert(
+            key,
+            RawValue {
+                value,
+                quoting: Quoting::Literal,
+            },
+        );
+    }
+
+    Ok(raw
+        .keys()
+        .filter(|key| key.starts_with(prefix))
+        .map(|key| (key.clone(), resolve(&raw, key, &mut Vec::default())))
+        .collect())
+}
+
+fn parse(
+    path: &Path,
+    content: &str,
+    output: &mut HashMap<String, RawValue>,
+) -> Result<(), EnvError> {
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let error = |message: &str| EnvError {
+            path: path.to_path_buf(),
+            line: idx + 1,
+            message: message.to_string(),
+        };
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected KEY=VALUE"))?;
+
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(error("invalid variable name"));
+        }
+
+        let value = value.trim();
+
+        let raw = if let Some(rest) = value.strip_prefix('\'') {
+            let end = rest.find('\'').ok_or_else(|| error("unterminated quote"))?;
+            RawValue {
+                value: rest[..end].to_string(),
+                quoting: Quoting::Literal,
+            }
+        } else if let Some(rest) = value.strip_prefix('"') {
+            let end = find_closing_quote(rest).ok_or_else(|| error("unterminated quote"))?;
+            RawValue {
+                value: rest[..end].to_string(),
+                quoting: Quoting::Double,
+            }
+        } else {
+            // Unquoted values end at a comment
+            let value = match value.find(" #") {
+                Some(idx) => &value[..idx],
+                None => value,
+            };
+            RawValue {
+                value: value.trim_end().to_string(),
+                quoting: Quoting::Plain,
+            }
+        };
+
+        output.insert(key.to_string(), raw);
+    }
+
+    Ok(())
+}
+
+fn find_closing_quote(input: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (idx, c) in input.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(idx),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// The value of `key` with references expanded. References to unknown
+/// variables, or back to a variable being expanded, are empty
+fn resolve(raw: &HashMap<String, RawValue>, key: &str, stack: &mut Vec<String>) -> String {
+    let value = match raw.get(key) {
+        Some(value) => value,
+        None => return String::default(),
+    };
+
+    if value.quoting == Quoting::Literal {
+        return value.value.clone();
+    }
+
+    if stack.iter().any(|k| k == key) {
+        log::warn!("circular reference to ${{{}}} in env", key);
+        return String::default();
+    }
+
+    stack.push(key.to_string());
+    let expanded = expand(raw, &value.value, value.quoting, stack);
+    stack.pop();
+
+    expanded
+}
+
+/// Expand `$VAR`, `${VAR}` and `${VAR:-default}`. `\$` is a literal `$`.
+/// Escapes of double quoted values are decoded in the same pass, so `\\$`
+/// is a backslash followed by an expansion
+fn expand(
+    raw: &HashMap<String, RawValue>,
+    input: &str,
+    quoting: Quoting,
+    stack: &mut Vec<String>,
+) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(idx) = rest.find(['$', '\\']) {
+        output.push_str(&rest[..idx]);
+        let tail = &rest[idx..];
+
+        if let Some(escaped) = tail.strip_prefix('\\') {
+            let mut chars = escaped.chars();
+            match (chars.next(), quoting) {
+                (Some('$'), _) => output.push('$'),
+                (Some('n'), Quoting::Double) => output.push('\n'),
+                (Some('r'), Quoting::Double) => output.push('\r'),
+                (Some('t'), Quoting::Double) => output.push('\t'),
+                (Some(c), Quoting::Double) => output.push(c),
+                _ => {
+                    output.push('\\');
+                    rest = escaped;
+                    continue;
+                }
+            }
+            rest = chars.as_str();
+            continue;
+        }
+
+        let tail = &tail[1..];
+
+        if let Some(inner) = tail.strip_prefix('{') {
+            let end = match inner.find('}') {
+                Some(end) => end,
+                None => {
+                    output.push_str("${");
+                    rest = inner;
+                    continue;
+                }
+            };
+
+            let (name, default) = match inner[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&inner[..end], None),
+            };
+
+            let value = resolve(raw, name, stack);
+            match default {
+                Some(default) if value.is_empty() => {
+                    output.push_str(&expand(raw, default, quoting, stack))
+                }
+                _ => output.push_str(&value),
+            }
+
+            rest = &inner[end + 1..];
+        } else {
+            let len = tail
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(tail.len());
+
+            if len == 0 {
+                output.push('$');
+            } else {
+                output.push_str(&resolve(raw, &tail[..len], stack));
+            }
+
+            rest = &tail[len..];
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryFileSystem;
+
+    static PREFIX: &str = "FAIRY_DOTENV_";
+
+    #[test]
+    fn parse_and_expand() {
+        let fs = MemoryFileSystem::new()
+            .with_file(
+                "/app/.env",
+                r#"
+# comment
+FAIRY_DOTENV_NAME=world
+FAIRY_DOTENV_PLAIN=hello $FAIRY_DOTENV_NAME # comment
+FAIRY_DOTENV_SINGLE='$FAIRY_DOTENV_NAME\n'
+FAIRY_DOTENV_DOUBLE="line\n${FAIRY_DOTENV_NAME}"
+FAIRY_DOTENV_BACKSLASH="a\\$FAIRY_DOTENV_NAME"
+FAIRY_DOTENV_DOLLAR="a\$FAIRY_DOTENV_NAME"
+FAIRY_DOTENV_QUOTE="say \"hi\""
+FAIRY_DOTENV_DEFAULT=${FAIRY_DOTENV_MISSING:-fall\back}
+FAIRY_DOTENV_LOOP=$FAIRY_DOTENV_LOOP
+export FAIRY_DOTENV_EXPORTED=1
+DOTENV_HIDDEN=hidden
+"#,
+            )
+            .with_file("/app/.env.dev", "FAIRY_DOTENV_NAME=dev");
+
+        let env = load_env(&fs, Path::new("/app"), Mode::Dev, PREFIX).unwrap();
+        let get = |key: &str| env[&format!("{}{}", PREFIX, key)].as_str();
+
+        assert_eq!(get("NAME"), "dev");
+        assert_eq!(get("PLAIN"), "hello dev");
+        assert_eq!(get("SINGLE"), "$FAIRY_DOTENV_NAME\\n");
+        assert_eq!(get("DOUBLE"), "line\ndev");
+        assert_eq!(get("BACKSLASH"), "a\\dev");
+        assert_eq!(get("DOLLAR"), "a$FAIRY_DOTENV_NAME");
+        assert_eq!(get("QUOTE"), "say \"hi\"");
+        assert_eq!(get("DEFAULT"), "fall\\back");
+        assert_eq!(get("LOOP"), "");
+        assert_eq!(get("EXPORTED"), "1");
+        assert!(!env.contains_key("DOTENV_HIDDEN"));
+
+        let env = load_env(&fs, Path::new("/app"), Mode::Build, PREFIX).unwrap();
+        assert_eq!(env["FAIRY_DOTENV_PLAIN"], "hello world");
+    }
+
+    #[test]
+    fn errors() {
+        let load = |content: &str| {
+            let fs = MemoryFileSystem::new().with_file("/app/.env", content);
+            load_env(&fs, Path::new("/app"), Mode::Dev, PREFIX)
+        };
+
+        let err = load("FAIRY_DOTENV_A=1\nFAIRY-DOTENV=2").unwrap_err();
+        assert_eq!(
+            (err.line, err.message.as_str()),
+            (2, "invalid variable name")
+        );
+
+        let err = load("FAIRY_DOTENV_A=\"open").unwrap_err();
+        assert_eq!((err.line, err.message.as_str()), (1, "unterminated quote"));
+
+        let err = load("\n\nFAIRY_DOTENV_A").unwrap_err();
+        assert_eq!((err.line, err.message.as_str()), (3, "expected KEY=VALUE"));
+    }
+}
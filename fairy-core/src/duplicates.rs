@@ -0,0 +1,50 @@
+use crate::{PeerWarning, VersionMismatch};
+use serde::Serialize;
+use std::{fmt, path::PathBuf};
+
+/// Problems with the installed packages, found in one walk of node_modules
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct InstallReport {
+    pub mismatches: Vec<VersionMismatch>,
+    pub duplicates: Vec<DuplicatePackage>,
+    pub peer_warnings: Vec<PeerWarning>,
+}
+
+/// One installed copy of a package
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PackageCopy {
+    pub path: PathBuf,
+    pub version: Option<String>,
+}
+
+/// A package installed in more than one location
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DuplicatePackage {
+    pub name: String,
+    pub copies: Vec<PackageCopy>,
+}
+
+impl DuplicatePackage {
+    /// Whether the copies are of different versions, rather than the same
+    /// version installed twice
+    pub fn has_multiple_versions(&self) -> bool {
+        self.copies
+            .iter()
+            .any(|copy| copy.version != self.copies[0].version)
+    }
+}
+
+impl fmt::Display for DuplicatePackage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is installed {} times:", self.name, self.copies.len())?;
+        for copy in &self.copies {
+            write!(
+                f,
+                "\n  {} at {:?}",
+                copy.version.as_deref().unwrap_or("?"),
+                copy.path
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+use crate::NotExported;
+use relative_path::RelativePathBuf;
+use std::path::PathBuf;
+use thiserror::Error as ThisError;
+
+#[derive(Debug, ThisError)]
+#[error("could not resolve '{specifier}' from '{importer}': {kind}")]
+pub struct ResolveError {
+    pub importer: RelativePathBuf,
+    pub specifier: String,
+    pub kind: ResolveErrorKind,
+}
+
+#[derive(Debug, ThisError)]
+pub enum ResolveErrorKind {
+    #[error("invalid package name")]
+    InvalidSpecifier,
+    #[error("package '{package}' is not installed")]
+    PackageNotInstalled { package: String },
+    #[error("'{module}' is a node built-in module, add a polyfill to use it in the browser")]
+    NodeBuiltin { module: String },
+    #[error("package '{package}' is not a dependency of {issuer}")]
+    UndeclaredDependency { package: String, issuer: String },
+    #[error("peer dependency '{package}' of {issuer} is not provided")]
+    MissingPeerDependency { package: String, issuer: String },
+    #[error("subpath '{subpath}' is not exported by package '{package}'")]
+    SubpathNotExported { package: String, subpath: String },
+    #[error("import '{import}' is not defined by package '{package}'")]
+    ImportNotDefined { package: String, import: String },
+    #[error("no package.json found for {path:?}")]
+    PackageJsonNotFound { path: PathBuf },
+    #[error("invalid package.json at {path:?}: {error}")]
+    InvalidPackageJson { path: PathBuf, error: anyhow::Error },
+    #[error("{path:?} is not inside the package at {root:?}")]
+    PathOutsidePackage { path: PathBuf, root: PathBuf },
+    #[error("file not found: {path:?} (tried extensions: {})", tried.join(", "))]
+    FileNotFound { path: PathBuf, tried: Vec<String> },
+}
+
+impl From<NotExported> for ResolveErrorKind {
+    fn from(err: NotExported) -> Self {
+        ResolveErrorKind::SubpathNotExported {
+            package: err.name,
+            subpath: err.subpath,
+        }
+    }
+}
+
+#[derive(Debug, ThisError)]
+pub enum ConfigError {
+    #[error("could not read {path:?}: {error}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        error: std::io::Error,
+    },
+    #[error("could not parse {path:?}: {message}")]
+    Syntax { path: PathBuf, message: String },
+    #[error("invalid config {path:?} at `{key}`: {message}")]
+    Invalid {
+        path: PathBuf,
+        key: String,
+        message: String,
+    },
+}
+
+#[derive(Debug, ThisError)]
+#[error("invalid env file {path:?} at line {line}: {message}")]
+pub struct EnvError {
+    pub path: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, ThisError)]
+#[error("could not load '{path}': {message}")]
+pub struct LoadError {
+    pub path: RelativePathBuf,
+    pub message: String,
+}
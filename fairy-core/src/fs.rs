@@ -0,0 +1,248 @@
+use parking_lot::RwLock;
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Dir,
+}
+
+/// The file access used for resolution and loading
+pub trait FileSystem: fmt::Debug + Send + Sync {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// The type of `path`, following links
+    fn file_type(&self, path: &Path) -> io::Result<FileType>;
+
+    /// The entries of a directory
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.file_type(path).is_ok()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        matches!(self.file_type(path), Ok(FileType::File))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        matches!(self.file_type(path), Ok(FileType::Dir))
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{:?} not found", path))
+}
+
+/// The disk
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsFileSystem;
+
+impl FileSystem for OsFileSystem {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn file_type(&self, path: &Path) -> io::Result<FileType> {
+        let meta = std::fs::metadata(path)?;
+        Ok(if meta.is_dir() {
+            FileType::Dir
+        } else {
+            FileType::File
+        })
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        path.canonicalize()
+    }
+}
+
+/// Files kept in memory. Directories exist implicitly, as the parents of
+/// the files they contain
+#[derive(Debug, Default)]
+pub struct MemoryFileSystem {
+    files: RwLock<BTreeMap<PathBuf, Vec<u8>>>,
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> MemoryFileSystem {
+        MemoryFileSystem::default()
+    }
+
+    /// Add a file, eg. when building a fixture tree
+    pub fn with_file(self, path: impl Into<PathBuf>, content: impl Into<Vec<u8>>) -> Self {
+        self.insert(path, content);
+        self
+    }
+
+    pub fn insert(&self, path: impl Into<PathBuf>, content: impl Into<Vec<u8>>) {
+        self.files.write().insert(path.into(), content.into());
+    }
+
+    /// Remove a file, or a directory with everything in it
+    pub fn remove(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        self.files.write().retain(|key, _| !key.starts_with(path));
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files
+            .read()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn file_type(&self, path: &Path) -> io::Result<FileType> {
+        let files = self.files.read();
+        // Paths order by component, so the contents of a directory follow it
+        match files.range(path.to_path_buf()..).next() {
+            Some((key, _)) if key == path => Ok(FileType::File),
+            Some((key, _)) if key.starts_with(path) => Ok(FileType::Dir),
+            _ => Err(not_found(path)),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        if self.file_type(path)? != FileType::Dir {
+            return Err(io::Error::other(format!("{:?} is not a directory", path)));
+        }
+
+        let mut entries = self
+            .files
+            .read()
+            .range(path.to_path_buf()..)
+            .take_while(|(key, _)| key.starts_with(path))
+            .filter_map(|(key, _)| {
+                let name = key.strip_prefix(path).ok()?.components().next()?;
+                Some(path.join(name))
+            })
+            .collect::<Vec<_>>();
+
+        entries.dedup();
+        Ok(entries)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.file_type(path).map(|_| path.to_path_buf())
+    }
+}
+
+/// Files of `upper` shadow those of `lower`, eg. generated files on top
+/// of the disk
+#[derive(Debug, Clone)]
+pub struct OverlayFileSystem {
+    upper: Arc<dyn FileSystem>,
+    lower: Arc<dyn FileSystem>,
+}
+
+impl OverlayFileSystem {
+    pub fn new(upper: Arc<dyn FileSystem>, lower: Arc<dyn FileSystem>) -> OverlayFileSystem {
+        OverlayFileSystem { upper, lower }
+    }
+}
+
+impl FileSystem for OverlayFileSystem {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.upper.read(path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => self.lower.read(path),
+            ret => ret,
+        }
+    }
+
+    fn file_type(&self, path: &Path) -> io::Result<FileType> {
+        self.upper
+            .file_type(path)
+            .or_else(|_| self.lower.file_type(path))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut entries = match (self.upper.read_dir(path), self.lower.read_dir(path)) {
+            (Err(err), Err(_)) => return Err(err),
+            (upper, lower) => {
+                let mut entries = upper.unwrap_or_default();
+                entries.extend(lower.unwrap_or_default());
+                entries
+            }
+        };
+
+        entries.sort();
+        entries.dedup();
+        Ok(entries)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        if self.upper.exists(path) {
+            self.upper.canonicalize(path)
+        } else {
+            self.lower.canonicalize(path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_directories() {
+        let fs = MemoryFileSystem::new()
+            .with_file("/app/src/a.js", "a")
+            .with_file("/app/src/nested/b.js", "b")
+            .with_file("/app/srcs.js", "");
+
+        assert_eq!(fs.file_type(Path::new("/app/src")).unwrap(), FileType::Dir);
+        assert_eq!(
+            fs.file_type(Path::new("/app/src/a.js")).unwrap(),
+            FileType::File
+        );
+        assert!(!fs.exists(Path::new("/app/sr")));
+        assert_eq!(
+            fs.read_dir(Path::new("/app/src")).unwrap(),
+            vec![
+                PathBuf::from("/app/src/a.js"),
+                PathBuf::from("/app/src/nested")
+            ]
+        );
+
+        fs.remove("/app/src/nested");
+        assert!(!fs.exists(Path::new("/app/src/nested/b.js")));
+        assert!(fs.is_file(Path::new("/app/src/a.js")));
+    }
+
+    #[test]
+    fn overlay_shadows_lower() {
+        let upper = MemoryFileSystem::new().with_file("/app/a.js", "upper");
+        let lower = MemoryFileSystem::new()
+            .with_file("/app/a.js", "lower")
+            .with_file("/app/b.js", "lower");
+        let fs = OverlayFileSystem::new(Arc::new(upper), Arc::new(lower));
+
+        assert_eq!(fs.read_to_string(Path::new("/app/a.js")).unwrap(), "upper");
+        assert_eq!(fs.read_to_string(Path::new("/app/b.js")).unwrap(), "lower");
+        assert_eq!(
+            fs.read_dir(Path::new("/app")).unwrap(),
+            vec![PathBuf::from("/app/a.js"), PathBuf::from("/app/b.js")]
+        );
+    }
+}
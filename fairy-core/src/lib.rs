@@ -1,11 +1,38 @@
+mod archive;
+mod cache;
 mod config;
+mod config_file;
+mod dotenv;
+mod duplicates;
+mod error;
+mod fs;
+mod loaders;
+mod lockfile;
 pub mod package;
+mod peers;
+mod pnp;
 mod resolver;
+mod trace;
+mod tsconfig;
 mod util;
+mod workspace;
 
 pub use self::{
+    archive::ArchiveFileSystem,
     config::*,
-    package::{ModuleType, PackageJson},
-    resolver::{ImportHint, Package, Resolver, TargetEnv},
+    config_file::{ConfigFile, Mode},
+    dotenv::{env_files, load_env, DEFAULT_ENV_PREFIX},
+    duplicates::{DuplicatePackage, InstallReport, PackageCopy},
+    error::*,
+    fs::{FileSystem, FileType, MemoryFileSystem, OsFileSystem, OverlayFileSystem},
+    loaders::{CssLoader, FileLoader, JsonLoader, RawLoader, UrlLoader},
+    lockfile::{Lockfile, LockfileKind, VersionMismatch},
+    package::{ModuleType, PackageJson, Workspaces},
+    peers::{satisfies, PeerWarning},
+    pnp::PnpManifest,
+    resolver::{Entry, ImportHint, NotExported, Package, Resolver, TargetEnv},
+    trace::{Explanation, TraceStep},
+    tsconfig::TsConfig,
     util::*,
+    workspace::Workspace,
 };
@@ -0,0 +1,329 @@
+use crate::LoadError;
+use relative_path::RelativePath;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// Turns files that aren't scripts into ES modules, so they can be
+/// imported. Configured as `{ "type": "json", ... }` entries of `plugins`
+#[typetag::serde(tag = "type")]
+pub trait FileLoader: fmt::Debug + Send + Sync {
+    /// Extensions, without the dot, of the files handled by the loader
+    fn extensions(&self) -> &[String];
+
+    /// The module for the file at `path`, relative to the project root
+    fn load(&self, path: &RelativePath, content: Vec<u8>) -> Result<String, LoadError>;
+
+    fn matches(&self, path: &RelativePath) -> bool {
+        match path.extension() {
+            Some(ext) => self.extensions().iter().any(|m| m == ext),
+            None => false,
+        }
+    }
+}
+
+fn extensions(list: &[&str]) -> Vec<String> {
+    list.iter().map(|m| m.to_string()).collect()
+}
+
+fn to_utf8(path: &RelativePath, content: Vec<u8>) -> Result<String, LoadError> {
+    String::from_utf8(content).map_err(|_| LoadError {
+        path: path.to_relative_path_buf(),
+        message: String::from("not valid utf-8"),
+    })
+}
+
+/// A string literal usable in JavaScript
+fn js_string(input: &str) -> String {
+    serde_json::to_string(input).expect("string")
+}
+
+/// The parsed document as default export
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonLoader {
+    #[serde(default = "JsonLoader::default_extensions")]
+    pub extensions: Vec<String>,
+    /// Also export the top-level keys of objects by name
+    #[serde(default)]
+    pub named_exports: bool,
+}
+
+impl JsonLoader {
+    fn default_extensions() -> Vec<String> {
+        extensions(&["json"])
+    }
+}
+
+impl Default for JsonLoader {
+    fn default() -> Self {
+        JsonLoader {
+            extensions: JsonLoader::default_extensions(),
+            named_exports: false,
+        }
+    }
+}
+
+#[typetag::serde(name = "json")]
+impl FileLoader for JsonLoader {
+    fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    fn load(&self, path: &RelativePath, content: Vec<u8>) -> Result<String, LoadError> {
+        let value: Value = serde_json::from_slice(&content).map_err(|err| LoadError {
+            path: path.to_relative_path_buf(),
+            message: err.to_string(),
+        })?;
+
+        let mut output = format!("const json = {};\nexport default json;\n", value);
+
+        let keys = match &value {
+            Value::Object(map) if self.named_exports => map
+                .keys()
+                .filter(|key| is_identifier(key) && *key != "default")
+                .collect::<Vec<_>>(),
+            _ => Vec::default(),
+        };
+
+        if !keys.is_empty() {
+            // Keys may be reserved words, which are only valid as export names
+            let mut names = Vec::with_capacity(keys.len());
+            for (idx, key) in keys.iter().enumerate() {
+                output.push_str(&format!("const $json{} = json[{}];\n", idx, js_string(key)));
+                names.push(format!("$json{} as {}", idx, key));
+            }
+            output.push_str(&format!("export {{ {} }};\n", names.join(", ")));
+        }
+
+        Ok(output)
+    }
+}
+
+fn is_identifier(input: &str) -> bool {
+    let mut chars = input.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// The stylesheet as default export, added to the document on import
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CssLoader {
+    #[serde(default = "CssLoader::default_extensions")]
+    pub extensions: Vec<String>,
+    /// Add a `<style>` element to the document head
+    #[serde(default = "CssLoader::default_inject")]
+    pub inject: bool,
+}
+
+impl CssLoader {
+    fn default_extensions() -> Vec<String> {
+        extensions(&["css"])
+    }
+
+    fn default_inject() -> bool {
+        true
+    }
+}
+
+impl Default for CssLoader {
+    fn default() -> Self {
+        CssLoader {
+            extensions: CssLoader::default_extensions(),
+            inject: CssLoader::default_inject(),
+        }
+    }
+}
+
+#[typetag::serde(name = "css")]
+impl FileLoader for CssLoader {
+    fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    fn load(&self, path: &RelativePath, content: Vec<u8>) -> Result<String, LoadError> {
+        let css = to_utf8(path, content)?;
+
+        let mut output = format!("const css = {};\n", js_string(&css));
+
+        if self.inject {
+            output.push_str(&format!(
+                concat!(
+                    "if (typeof document !== \"undefined\") {{\n",
+                    "  const style = document.createElement(\"style\");\n",
+                    "  style.setAttribute(\"data-fairy-path\", {});\n",
+                    "  style.textContent = css;\n",
+                    "  document.head.appendChild(style);\n",
+                    "}}\n"
+                ),
+                js_string(&format!("/{}", path))
+            ));
+        }
+
+        output.push_str("export default css;\n");
+
+        Ok(output)
+    }
+}
+
+/// The text of the file as default export
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RawLoader {
+    #[serde(default = "RawLoader::default_extensions")]
+    pub extensions: Vec<String>,
+}
+
+impl RawLoader {
+    fn default_extensions() -> Vec<String> {
+        extensions(&["txt"])
+    }
+}
+
+impl Default for RawLoader {
+    fn default() -> Self {
+        RawLoader {
+            extensions: RawLoader::default_extensions(),
+        }
+    }
+}
+
+#[typetag::serde(name = "raw")]
+impl FileLoader for RawLoader {
+    fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    fn load(&self, path: &RelativePath, content: Vec<u8>) -> Result<String, LoadError> {
+        let text = to_utf8(path, content)?;
+        Ok(format!("export default {};\n", js_string(&text)))
+    }
+}
+
+/// The url the file is served at as default export
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UrlLoader {
+    #[serde(default = "UrlLoader::default_extensions")]
+    pub extensions: Vec<String>,
+}
+
+impl UrlLoader {
+    fn default_extensions() -> Vec<String> {
+        extensions(&["jpg", "jpeg", "png", "gif", "webp", "ico", "svg"])
+    }
+}
+
+impl Default for UrlLoader {
+    fn default() -> Self {
+        UrlLoader {
+            extensions: UrlLoader::default_extensions(),
+        }
+    }
+}
+
+#[typetag::serde(name = "url")]
+impl FileLoader for UrlLoader {
+    fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    fn load(&self, path: &RelativePath, _content: Vec<u8>) -> Result<String, LoadError> {
+        Ok(format!(
+            "export default {};\n",
+            js_string(&format!("/{}", path))
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(loader: &dyn FileLoader, path: &str, content: &str) -> String {
+        loader
+            .load(RelativePath::new(path), content.as_bytes().to_vec())
+            .expect("load")
+    }
+
+    #[test]
+    fn matches_extensions() {
+        let loader = JsonLoader::default();
+        assert!(loader.matches(RelativePath::new("src/data.json")));
+        assert!(!loader.matches(RelativePath::new("src/data.js")));
+        assert!(!loader.matches(RelativePath::new("src/json")));
+    }
+
+    #[test]
+    fn json() {
+        let json = r#"{"name":"fairy","default":1,"if":2,"not-ident":3}"#;
+
+        let output = load(&JsonLoader::default(), "data.json", json);
+        assert!(output.starts_with("const json = {"));
+        assert!(output.contains("export default json;"));
+        assert!(!output.contains("export {"));
+
+        let loader = JsonLoader {
+            named_exports: true,
+            ..JsonLoader::default()
+        };
+        let output = load(&loader, "data.json", json);
+        assert!(output.contains("export { $json0 as if, $json1 as name };"));
+
+        let err = loader
+            .load(RelativePath::new("data.json"), b"{".to_vec())
+            .unwrap_err();
+        assert_eq!(err.path, RelativePath::new("data.json"));
+    }
+
+    #[test]
+    fn css() {
+        let output = load(
+            &CssLoader::default(),
+            "src/style.css",
+            "a { content: \"x\"; }",
+        );
+        assert!(output.starts_with("const css = \"a { content: \\\"x\\\"; }\";\n"));
+        assert!(output.contains("\"/src/style.css\""));
+        assert!(output.ends_with("export default css;\n"));
+
+        let loader = CssLoader {
+            inject: false,
+            ..CssLoader::default()
+        };
+        let output = load(&loader, "src/style.css", "a {}");
+        assert!(!output.contains("document"));
+
+        assert!(loader
+            .load(RelativePath::new("style.css"), vec![0xff])
+            .is_err());
+    }
+
+    #[test]
+    fn raw_and_url() {
+        assert_eq!(
+            load(&RawLoader::default(), "notes.txt", "line\n"),
+            "export default \"line\\n\";\n"
+        );
+        assert_eq!(
+            load(&UrlLoader::default(), "src/logo.png", ""),
+            "export default \"/src/logo.png\";\n"
+        );
+    }
+
+    #[test]
+    fn deserialize() {
+        let loaders: Vec<Box<dyn FileLoader>> = serde_json::from_str(
+            r#"[{"type": "json", "named_exports": true}, {"type": "url", "extensions": ["svg"]}]"#,
+        )
+        .expect("loaders");
+
+        assert!(loaders[0].matches(RelativePath::new("a.json")));
+        assert!(loaders[1].matches(RelativePath::new("a.svg")));
+        assert!(!loaders[1].matches(RelativePath::new("a.png")));
+    }
+}
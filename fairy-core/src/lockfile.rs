@@ -0,0 +1,473 @@
+use crate::FileSystem;
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+pub static YARN_LOCK: &str = "yarn.lock";
+
+pub static PACKAGE_LOCK_JSON: &str = "package-lock.json";
+
+pub static PNPM_LOCK_YAML: &str = "pnpm-lock.yaml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LockfileKind {
+    Yarn,
+    Npm,
+    Pnpm,
+}
+
+/// The versions a package manager resolved for each package
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lockfile {
+    pub path: PathBuf,
+    pub kind: LockfileKind,
+    packages: HashMap<String, Vec<String>>,
+}
+
+impl Lockfile {
+    /// Find the lockfile of the project at `path`, which may be in the
+    /// root of a surrounding workspace
+    pub fn find(fs: &dyn FileSystem, path: &Path) -> Option<Lockfile> {
+        let mut parent = Some(path);
+        while let Some(p) = parent {
+            for name in [YARN_LOCK, PACKAGE_LOCK_JSON, PNPM_LOCK_YAML] {
+                let lockfile = p.join(name);
+                if !fs.is_file(&lockfile) {
+                    continue;
+                }
+
+                match Lockfile::load(fs, &lockfile) {
+                    Ok(ret) => return Some(ret),
+                    Err(err) => log::error!("could not load lockfile: {:?}", err),
+                }
+            }
+            parent = p.parent();
+        }
+        None
+    }
+
+    pub fn load(fs: &dyn FileSystem, path: &Path) -> anyhow::Result<Lockfile> {
+        let content = fs
+            .read_to_string(path)
+            .context(format!("at path: {:?}", path))?;
+
+        let kind = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if name == YARN_LOCK => LockfileKind::Yarn,
+            Some(name) if name == PACKAGE_LOCK_JSON => LockfileKind::Npm,
+            Some(name) if name == PNPM_LOCK_YAML => LockfileKind::Pnpm,
+            _ => bail!("unknown lockfile: {:?}", path),
+        };
+
+        let mut lockfile = Lockfile {
+            path: path.to_path_buf(),
+            kind,
+            packages: HashMap::default(),
+        };
+
+        match kind {
+            LockfileKind::Yarn => lockfile.parse_yarn(&content),
+            LockfileKind::Npm => lockfile
+                .parse_npm(&content)
+                .context(format!("invalid lockfile: {:?}", path))?,
+            LockfileKind::Pnpm => lockfile.parse_pnpm(&content),
+        }
+
+        Ok(lockfile)
+    }
+
+    /// Resolved versions of `name`. More than one when the package is
+    /// installed in several versions
+    pub fn versions(&self, name: &str) -> &[String] {
+        self.packages
+            .get(name)
+            .map(|versions| versions.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn packages(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.packages
+            .iter()
+            .map(|(name, versions)| (name.as_str(), versions.as_slice()))
+    }
+
+    fn insert(&mut self, name: &str, version: &str) {
+        let versions = self.packages.entry(name.to_string()).or_default();
+        if !versions.iter().any(|v| v == version) {
+            versions.push(version.to_string());
+        }
+    }
+
+    /// Both yarn berry (`"name@npm:^1.0.0":` / `version: 1.0.1`) and
+    /// classic (`name@^1.0.0:` / `version "1.0.1"`) entries
+    fn parse_yarn(&mut self, content: &str) {
+        let mut names: Vec<String> = Vec::default();
+
+        for line in content.lines() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+
+            if !line.starts_with(' ') {
+                names = line
+                    .trim_end_matches(':')
+                    .split(", ")
+                    .map(unquote)
+                    .filter(|descriptor| !is_local(descriptor))
+                    .filter_map(descriptor_name)
+                    .map(String::from)
+                    .collect();
+                continue;
+            }
+
+            let line = line.trim();
+            if !line.starts_with("version") || line.starts_with("versions") {
+                continue;
+            }
+
+            let version = unquote(line["version".len()..].trim_start_matches(':').trim());
+            for name in std::mem::take(&mut names) {
+                self.insert(&name, version);
+            }
+        }
+    }
+
+    /// `packages` keyed by install path (v2, v3), or the nested
+    /// `dependencies` of v1
+    fn parse_npm(&mut self, content: &str) -> anyhow::Result<()> {
+        #[derive(Deserialize)]
+        struct NpmPackage {
+            name: Option<String>,
+            version: Option<String>,
+            #[serde(default)]
+            dependencies: HashMap<String, serde_json::Value>,
+        }
+
+        #[derive(Deserialize)]
+        struct NpmLockfile {
+            #[serde(default)]
+            packages: HashMap<String, NpmPackage>,
+            #[serde(default)]
+            dependencies: HashMap<String, serde_json::Value>,
+        }
+
+        fn walk_v1(lockfile: &mut Lockfile, dependencies: HashMap<String, serde_json::Value>) {
+            for (name, value) in dependencies {
+                if let Ok(package) = serde_json::from_value::<NpmPackage>(value) {
+                    if let Some(version) = &package.version {
+                        lockfile.insert(&name, version);
+                    }
+                    walk_v1(lockfile, package.dependencies);
+                }
+            }
+        }
+
+        let json: NpmLockfile = serde_json::from_str(content)?;
+
+        if json.packages.is_empty() {
+            walk_v1(self, json.dependencies);
+            return Ok(());
+        }
+
+        for (path, package) in json.packages {
+            let name = match path.rsplit_once("node_modules/") {
+                Some((_, name)) => package.name.as_deref().unwrap_or(name),
+                // The project and its workspaces
+                None => continue,
+            };
+
+            if let Some(version) = &package.version {
+                self.insert(name, version);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keys of `packages`: `/name/1.0.0_peer@1.0.0` (v5),
+    /// `/name@1.0.0(peer@1.0.0)` (v6) and `name@1.0.0` (v9)
+    fn parse_pnpm(&mut self, content: &str) {
+        let mut in_packages = false;
+
+        for line in content.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if !line.starts_with(' ') {
+                in_packages = line.trim_end() == "packages:";
+                continue;
+            }
+
+            if !in_packages || line.starts_with("   ") || !line.trim_end().ends_with(':') {
+                continue;
+            }
+
+            let key = unquote(line.trim().trim_end_matches(':'));
+            let key = key.trim_start_matches('/');
+            let key = key.split('(').next().unwrap_or(key);
+
+            let v5 = key
+                .rsplit_once('/')
+                .filter(|(_, version)| version.starts_with(|c: char| c.is_ascii_digit()));
+
+            let (name, version) = match v5 {
+                Some((name, version)) => (name, version.split('_').next().unwrap_or(version)),
+                None => match key.rfind('@').filter(|idx| *idx > 0) {
+                    Some(idx) => (&key[..idx], &key[idx + 1..]),
+                    None => continue,
+                },
+            };
+
+            self.insert(name, version);
+        }
+    }
+}
+
+fn unquote(input: &str) -> &str {
+    input.trim().trim_matches(|c| c == '"' || c == '\'')
+}
+
+/// Workspaces and linked directories (`name@workspace:packages/name`) are
+/// installed from source, their version isn't locked
+fn is_local(descriptor: &str) -> bool {
+    let range = match descriptor.get(1..).and_then(|rest| rest.find('@')) {
+        Some(idx) => &descriptor[idx + 2..],
+        None => return false,
+    };
+
+    ["workspace:", "link:", "portal:"]
+        .iter()
+        .any(|protocol| range.starts_with(protocol))
+}
+
+/// The package name of a descriptor like `@scope/name@npm:^1.0.0`
+fn descriptor_name(descriptor: &str) -> Option<&str> {
+    let idx = descriptor.get(1..)?.find('@')? + 1;
+    Some(&descriptor[..idx])
+}
+
+/// A package in node_modules with a version the lockfile doesn't list
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VersionMismatch {
+    pub package: String,
+    pub path: PathBuf,
+    pub installed: String,
+    pub locked: Vec<String>,
+}
+
+impl fmt::Display for VersionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}@{} installed at {:?} does not match the lockfile ({})",
+            self.package,
+            self.installed,
+            self.path,
+            self.locked.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryFileSystem;
+
+    fn load(name: &str, content: &str) -> Lockfile {
+        let path = Path::new("/app").join(name);
+        let fs = MemoryFileSystem::new().with_file(&path, content);
+        Lockfile::load(&fs, &path).unwrap()
+    }
+
+    #[test]
+    fn yarn_berry() {
+        let lockfile = load(
+            YARN_LOCK,
+            r#"# This file is generated by running "yarn install" inside your project.
+
+__metadata:
+  version: 6
+
+"@scope/lib@npm:^1.0.0, @scope/lib@npm:^1.1.0":
+  version: 1.2.0
+  resolution: "@scope/lib@npm:1.2.0"
+
+"react@npm:^17.0.0":
+  version: 17.0.2
+  dependencies:
+    loose-envify: ^1.1.0
+
+"react@npm:^18.0.0":
+  version: 18.2.0
+
+"app@workspace:.":
+  version: 0.0.0-use.local
+  resolution: "app@workspace:."
+  languageName: unknown
+  linkType: soft
+
+"@fairy/core@workspace:^, @fairy/core@workspace:packages/core":
+  version: 0.0.0-use.local
+  resolution: "@fairy/core@workspace:packages/core"
+
+"local@link:../local::locator=app%40workspace%3A.":
+  version: 0.0.0-use.local
+"#,
+        );
+
+        assert_eq!(lockfile.kind, LockfileKind::Yarn);
+        assert_eq!(lockfile.versions("@scope/lib"), ["1.2.0"]);
+        assert_eq!(lockfile.versions("react"), ["17.0.2", "18.2.0"]);
+        assert!(lockfile.versions("__metadata").is_empty());
+        assert!(lockfile.versions("app").is_empty());
+        assert!(lockfile.versions("@fairy/core").is_empty());
+        assert!(lockfile.versions("local").is_empty());
+        assert!(lockfile.versions("loose-envify").is_empty());
+    }
+
+    #[test]
+    fn yarn_classic() {
+        let lockfile = load(
+            YARN_LOCK,
+            r#"# yarn lockfile v1
+
+
+"@babel/code-frame@^7.0.0", "@babel/code-frame@^7.10.4":
+  version "7.12.13"
+  resolved "https://registry.yarnpkg.com/@babel/code-frame/-/code-frame-7.12.13.tgz"
+
+lodash@^4.17.20:
+  version "4.17.21"
+"#,
+        );
+
+        assert_eq!(lockfile.versions("@babel/code-frame"), ["7.12.13"]);
+        assert_eq!(lockfile.versions("lodash"), ["4.17.21"]);
+    }
+
+    #[test]
+    fn npm_v1() {
+        let lockfile = load(
+            PACKAGE_LOCK_JSON,
+            r#"{
+                "name": "app",
+                "lockfileVersion": 1,
+                "dependencies": {
+                    "lib": {
+                        "version": "2.0.0",
+                        "dependencies": {
+                            "dep": { "version": "1.0.0" }
+                        }
+                    },
+                    "dep": { "version": "2.0.0" }
+                }
+            }"#,
+        );
+
+        assert_eq!(lockfile.kind, LockfileKind::Npm);
+        assert_eq!(lockfile.versions("lib"), ["2.0.0"]);
+
+        let mut versions = lockfile.versions("dep").to_vec();
+        versions.sort();
+        assert_eq!(versions, ["1.0.0", "2.0.0"]);
+    }
+
+    #[test]
+    fn npm_v2() {
+        let lockfile = load(
+            PACKAGE_LOCK_JSON,
+            r#"{
+                "name": "app",
+                "lockfileVersion": 2,
+                "packages": {
+                    "": { "name": "app", "version": "1.0.0" },
+                    "packages/ui": { "name": "ui", "version": "0.1.0" },
+                    "node_modules/@scope/lib": { "version": "1.2.0" },
+                    "node_modules/lib/node_modules/dep": { "version": "1.0.0" },
+                    "node_modules/alias": { "name": "real", "version": "3.0.0" }
+                },
+                "dependencies": {
+                    "ignored": { "version": "9.9.9" }
+                }
+            }"#,
+        );
+
+        assert_eq!(lockfile.versions("@scope/lib"), ["1.2.0"]);
+        assert_eq!(lockfile.versions("dep"), ["1.0.0"]);
+        assert_eq!(lockfile.versions("real"), ["3.0.0"]);
+        assert!(lockfile.versions("app").is_empty());
+        assert!(lockfile.versions("ui").is_empty());
+        assert!(lockfile.versions("ignored").is_empty());
+    }
+
+    #[test]
+    fn pnpm() {
+        let v5 = r#"lockfileVersion: 5.4
+
+importers:
+
+  .:
+    specifiers:
+      react: ^18.0.0
+
+packages:
+
+  /@scope/lib/1.2.0_react@18.2.0:
+    resolution: {integrity: sha512-abc}
+    peerDependencies:
+      react: ^18.0.0
+
+  /react/18.2.0:
+    resolution: {integrity: sha512-def}
+"#;
+
+        let v6 = r#"lockfileVersion: '6.0'
+
+packages:
+
+  /@scope/lib@1.2.0(react@18.2.0):
+    resolution: {integrity: sha512-abc}
+
+  /react@18.2.0:
+    resolution: {integrity: sha512-def}
+"#;
+
+        let v9 = r#"lockfileVersion: '9.0'
+
+packages:
+
+  '@scope/lib@1.2.0':
+    resolution: {integrity: sha512-abc}
+
+  react@18.2.0:
+    resolution: {integrity: sha512-def}
+
+snapshots:
+
+  other@1.0.0: {}
+"#;
+
+        for content in [v5, v6, v9] {
+            let lockfile = load(PNPM_LOCK_YAML, content);
+            assert_eq!(lockfile.kind, LockfileKind::Pnpm);
+            assert_eq!(lockfile.versions("@scope/lib"), ["1.2.0"], "{}", content);
+            assert_eq!(lockfile.versions("react"), ["18.2.0"], "{}", content);
+            assert!(lockfile.versions("other").is_empty());
+        }
+    }
+
+    #[test]
+    fn find_in_workspace_root() {
+        let fs = MemoryFileSystem::new()
+            .with_file("/repo/yarn.lock", "lib@^1.0.0:\n  version \"1.0.0\"\n");
+
+        let lockfile = Lockfile::find(&fs, Path::new("/repo/packages/app")).unwrap();
+        assert_eq!(lockfile.path, Path::new("/repo/yarn.lock"));
+        assert_eq!(lockfile.versions("lib"), ["1.0.0"]);
+    }
+}
@@ -1,58 +1,90 @@
+use indexmap::IndexMap;
 use std::collections::HashMap;
 
+use crate::FileSystem;
 use anyhow::Context;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
-pub static PACKAGE_JSON: &'static str = "package.json";
+pub static PACKAGE_JSON: &str = "package.json";
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub enum ModuleType {
     #[serde(alias = "module")]
     Esm,
     #[serde(alias = "commonjs")]
+    #[default]
     Commonjs,
 }
 
-impl Default for ModuleType {
-    fn default() -> Self {
-        ModuleType::Commonjs
-    }
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BrowserTarget {
+    Str(String),
+    Bool(bool),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum Browser {
     Str(String),
-    Obj(HashMap<String, String>),
+    Obj(HashMap<String, BrowserTarget>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Workspaces {
+    List(Vec<String>),
+    Obj { packages: Vec<String> },
+}
+
+impl Workspaces {
+    pub fn patterns(&self) -> &[String] {
+        match self {
+            Workspaces::List(patterns) => patterns,
+            Workspaces::Obj { packages } => packages,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum Exports {
+    Null,
     Str(String),
-    Obj(HashMap<String, Exports>),
+    Array(Vec<Exports>),
+    /// In the order of the package.json, conditions are matched by it
+    Obj(IndexMap<String, Exports>),
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct PackageJson {
+    /// Nested package.json files, used to point at a directory entry, may leave it out
+    #[serde(default)]
     pub name: String,
+    pub version: Option<String>,
     pub main: Option<String>,
     pub browser: Option<Browser>,
     pub module: Option<String>,
     pub exports: Option<Exports>,
+    pub imports: Option<IndexMap<String, Exports>>,
     #[serde(rename = "type", default)]
     pub kind: ModuleType,
     #[serde(default)]
     pub dependencies: HashMap<String, String>,
     #[serde(rename = "peerDependencies", default)]
     pub peer_dependencies: HashMap<String, String>,
+    pub workspaces: Option<Workspaces>,
+    /// Remaining fields, used for custom main fields
+    #[serde(flatten)]
+    pub fields: HashMap<String, serde_json::Value>,
 }
 
 impl PackageJson {
-    pub fn load(root: &Path) -> anyhow::Result<PackageJson> {
-        let data =
-            std::fs::read(root.join(PACKAGE_JSON)).context(format!("at root: {:?}", root))?;
+    pub fn load(fs: &dyn FileSystem, root: &Path) -> anyhow::Result<PackageJson> {
+        let data = fs
+            .read(&root.join(PACKAGE_JSON))
+            .context(format!("at root: {:?}", root))?;
         Ok(serde_json::from_slice(&data)?)
     }
 }
@@ -0,0 +1,157 @@
+use semver::{Version, VersionReq};
+use serde::Serialize;
+use std::{fmt, path::PathBuf};
+
+/// A peer dependency of an installed package that isn't satisfied
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PeerWarning {
+    Missing {
+        package: String,
+        path: PathBuf,
+        peer: String,
+        range: String,
+    },
+    Incompatible {
+        package: String,
+        path: PathBuf,
+        peer: String,
+        range: String,
+        installed: String,
+    },
+}
+
+impl fmt::Display for PeerWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeerWarning::Missing {
+                package,
+                peer,
+                range,
+                ..
+            } => write!(
+                f,
+                "{} requires peer dependency {}@{}, which is not installed",
+                package, peer, range
+            ),
+            PeerWarning::Incompatible {
+                package,
+                peer,
+                range,
+                installed,
+                ..
+            } => write!(
+                f,
+                "{} requires peer dependency {}@{}, but {} is installed",
+                package, peer, range, installed
+            ),
+        }
+    }
+}
+
+/// Whether `version` is in the npm `range`. `None` when either can't be
+/// parsed, eg. `workspace:*` or a git url
+pub fn satisfies(version: &str, range: &str) -> Option<bool> {
+    let version = Version::parse(version.trim().trim_start_matches('v')).ok()?;
+
+    for alternative in range.split("||") {
+        let req = VersionReq::parse(&npm_range(alternative)?).ok()?;
+        if req.matches(&version) {
+            return Some(true);
+        }
+    }
+
+    Some(false)
+}
+
+/// Translate an npm range to the comparators `semver` understands:
+/// comma separated, with bare versions matching exactly and hyphen
+/// ranges spelled out
+fn npm_range(range: &str) -> Option<String> {
+    let mut tokens = Vec::<String>::default();
+    let mut operator = None;
+
+    for token in range.split_whitespace() {
+        // `>= 1.2` is `>=1.2`
+        if token.chars().all(|c| "<>=~^".contains(c)) {
+            operator = Some(token);
+            continue;
+        }
+        match operator.take() {
+            Some(op) => tokens.push(format!("{}{}", op, token)),
+            None => tokens.push(token.to_string()),
+        }
+    }
+
+    let mut comparators = Vec::default();
+    let mut iter = tokens.iter().peekable();
+
+    while let Some(token) = iter.next() {
+        if iter.peek().map(|next| *next == "-").unwrap_or(false) {
+            iter.next();
+            let upper = iter.next()?;
+            comparators.push(format!(">={}", token.trim_start_matches('v')));
+            comparators.push(format!("<={}", upper.trim_start_matches('v')));
+            continue;
+        }
+
+        let token = token.trim_start_matches('=').trim_start_matches('v');
+        match token {
+            "" | "*" | "x" | "X" | "latest" => comparators.push(String::from("*")),
+            token if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                comparators.push(format!("={}", token))
+            }
+            token => comparators.push(token.to_string()),
+        }
+    }
+
+    if comparators.is_empty() {
+        comparators.push(String::from("*"));
+    }
+
+    Some(comparators.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn npm_ranges() {
+        let cases = [
+            ("1.2.3", "^1.0.0", true),
+            ("2.0.0", "^1.0.0", false),
+            ("1.2.3", "~1.2.0", true),
+            ("1.3.0", "~1.2.0", false),
+            ("1.2.3", "1.2.3", true),
+            ("1.2.4", "1.2.3", false),
+            ("v1.2.3", "=1.2.3", true),
+            ("1.2.3", "*", true),
+            ("1.2.3", "", true),
+            ("1.2.3", "x", true),
+            ("1.2.3", ">= 1.0.0 < 2", true),
+            ("2.0.0", ">=1.0.0 <2.0.0", false),
+            ("1.5.0", "1.0.0 - 2.0.0", true),
+            ("2.0.0", "1.0.0 - 2.0.0", true),
+            ("2.0.1", "1.0.0 - 2.0.0", false),
+            ("17.0.2", "^16.8.0 || ^17.0.0 || ^18.0.0", true),
+            ("15.0.0", "^16.8.0 || ^17.0.0", false),
+        ];
+
+        for (version, range, expected) in cases {
+            assert_eq!(
+                satisfies(version, range),
+                Some(expected),
+                "{} in {:?}",
+                version,
+                range
+            );
+        }
+    }
+
+    #[test]
+    fn unparsable() {
+        assert_eq!(satisfies("1.0.0", "workspace:*"), None);
+        assert_eq!(satisfies("latest", "^1.0.0"), None);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{FileSystem, ResolveErrorKind};
+use anyhow::{bail, Context};
+use relative_path::RelativePath;
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+pub static PNP_DATA_JSON: &str = ".pnp.data.json";
+
+pub static PNP_CJS: &str = ".pnp.cjs";
+
+/// A package name and reference. The project itself is `(None, None)`
+type Locator = (Option<String>, Option<String>);
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+enum Reference {
+    Direct(String),
+    /// A dependency installed under another name, eg. `npm:other@1.0.0`
+    Aliased(String, String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PackageInformation {
+    package_location: String,
+    #[serde(default)]
+    package_dependencies: Vec<(String, Option<Reference>)>,
+}
+
+/// Packages by name, then by reference
+type PackageRegistry = Vec<(Option<String>, Vec<(Option<String>, PackageInformation)>)>;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PnpData {
+    #[serde(default)]
+    enable_top_level_fallback: bool,
+    #[serde(default)]
+    fallback_pool: Vec<(String, Option<Reference>)>,
+    #[serde(default)]
+    fallback_exclusion_list: Vec<(String, Vec<String>)>,
+    package_registry_data: PackageRegistry,
+}
+
+#[derive(Debug)]
+struct PnpPackage {
+    location: PathBuf,
+    /// `None` for peer dependencies the parent doesn't provide
+    dependencies: HashMap<String, Option<Reference>>,
+}
+
+/// The package map of a yarn Plug'n'Play install
+#[derive(Debug)]
+pub struct PnpManifest {
+    enable_top_level_fallback: bool,
+    fallback_pool: HashMap<String, Option<Reference>>,
+    fallback_exclusions: HashMap<String, HashSet<String>>,
+    packages: HashMap<Locator, PnpPackage>,
+    /// Package locations, longest first
+    locations: Vec<(PathBuf, Locator)>,
+}
+
+impl PnpManifest {
+    /// Load a `.pnp.data.json` or the data embedded in a `.pnp.cjs`
+    pub fn load(fs: &dyn FileSystem, path: &Path) -> anyhow::Result<PnpManifest> {
+        let content = fs
+            .read_to_string(path)
+            .context(format!("at path: {:?}", path))?;
+
+        let json = if path.extension().map(|ext| ext == "cjs").unwrap_or(false) {
+            extract_data(&content).with_context(|| format!("no pnp data found in {:?}", path))?
+        } else {
+            content
+        };
+
+        let data: PnpData =
+            serde_json::from_str(&json).context(format!("invalid pnp data: {:?}", path))?;
+
+        let dir = path.parent().unwrap_or_else(|| Path::new("/"));
+
+        let mut packages = HashMap::default();
+        let mut locations = Vec::default();
+
+        for (name, references) in data.package_registry_data {
+            for (reference, info) in references {
+                let locator = (name.clone(), reference);
+                let location = RelativePath::new(&info.package_location).to_logical_path(dir);
+
+                locations.push((location.clone(), locator.clone()));
+                packages.insert(
+                    locator,
+                    PnpPackage {
+                        location,
+                        dependencies: info.package_dependencies.into_iter().collect(),
+                    },
+                );
+            }
+        }
+
+        // The project shares its location with the root workspace, prefer the latter
+        locations.sort_by_key(|(location, (name, _))| {
+            (
+                std::cmp::Reverse(location.as_os_str().len()),
+                name.is_none(),
+            )
+        });
+
+        Ok(PnpManifest {
+            enable_top_level_fallback: data.enable_top_level_fallback,
+            fallback_pool: data.fallback_pool.into_iter().collect(),
+            fallback_exclusions: data
+                .fallback_exclusion_list
+                .into_iter()
+                .map(|(name, references)| (name, references.into_iter().collect()))
+                .collect(),
+            packages,
+            locations,
+        })
+    }
+
+    fn find_locator(&self, path: &Path) -> Option<&Locator> {
+        self.locations
+            .iter()
+            .find(|(location, _)| path.starts_with(location))
+            .map(|(_, locator)| locator)
+    }
+
+    fn fallback(&self, issuer: &Locator, name: &str) -> Option<&Option<Reference>> {
+        if !self.enable_top_level_fallback {
+            return None;
+        }
+
+        if let (Some(issuer_name), Some(reference)) = issuer {
+            let excluded = self
+                .fallback_exclusions
+                .get(issuer_name)
+                .map(|references| references.contains(reference))
+                .unwrap_or(false);
+            if excluded {
+                return None;
+            }
+        }
+
+        self.packages
+            .get(&(None, None))
+            .and_then(|top_level| top_level.dependencies.get(name))
+            .or_else(|| self.fallback_pool.get(name))
+    }
+
+    /// Location of the package `name` as seen from `issuer`. `None` when
+    /// `issuer` isn't part of the install.
+    pub fn resolve(&self, issuer: &Path, name: &str) -> Result<Option<PathBuf>, ResolveErrorKind> {
+        let issuer_locator = match self.find_locator(issuer) {
+            Some(locator) => locator,
+            None => return Ok(None),
+        };
+
+        let issuer_name = || {
+            issuer_locator
+                .0
+                .clone()
+                .unwrap_or_else(|| String::from("the project"))
+        };
+
+        let reference = self.packages[issuer_locator]
+            .dependencies
+            .get(name)
+            .or_else(|| self.fallback(issuer_locator, name))
+            .ok_or_else(|| ResolveErrorKind::UndeclaredDependency {
+                package: name.to_string(),
+                issuer: issuer_name(),
+            })?
+            .as_ref()
+            .ok_or_else(|| ResolveErrorKind::MissingPeerDependency {
+                package: name.to_string(),
+                issuer: issuer_name(),
+            })?;
+
+        let locator = match reference {
+            Reference::Direct(reference) => (Some(name.to_string()), Some(reference.clone())),
+            Reference::Aliased(name, reference) => (Some(name.clone()), Some(reference.clone())),
+        };
+
+        match self.packages.get(&locator) {
+            Some(package) => Ok(Some(package.location.clone())),
+            None => Err(ResolveErrorKind::PackageNotInstalled {
+                package: name.to_string(),
+            }),
+        }
+    }
+}
+
+/// The manifest is embedded in `.pnp.cjs` as a single quoted string,
+/// passed to `JSON.parse` (yarn 2, 3) or assigned to `RAW_RUNTIME_STATE`
+/// (yarn 4)
+fn extract_data(content: &str) -> anyhow::Result<String> {
+    let start = if let Some(idx) = content.find("JSON.parse('") {
+        idx + "JSON.parse('".len()
+    } else if let Some(idx) = content.find("RAW_RUNTIME_STATE") {
+        let rest = &content[idx + "RAW_RUNTIME_STATE".len()..];
+        let value = rest.trim_start().strip_prefix('=').map(str::trim_start);
+        match value.and_then(|value| value.strip_prefix('\'')) {
+            Some(value) => content.len() - value.len(),
+            None => bail!("RAW_RUNTIME_STATE is not a string"),
+        }
+    } else {
+        bail!("missing JSON.parse call or RAW_RUNTIME_STATE")
+    };
+
+    let mut output = String::default();
+    let mut chars = content[start..].chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => return Ok(output),
+            '\\' => match chars.next() {
+                // Line continuation
+                Some('\n') => {}
+                Some('\r') => {
+                    chars.next();
+                }
+                Some('n') => output.push('\n'),
+                Some('r') => output.push('\r'),
+                Some('t') => output.push('\t'),
+                Some(c) => output.push(c),
+                None => break,
+            },
+            c => output.push(c),
+        }
+    }
+
+    bail!("unterminated string")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_json_parse() {
+        let content = "const RAW = JSON.parse('{\\\n  \"a\": \"it\\'s\"\\\n}');";
+        assert_eq!(extract_data(content).unwrap(), "{  \"a\": \"it's\"}");
+    }
+
+    #[test]
+    fn extract_raw_runtime_state() {
+        let content = "const RAW_RUNTIME_STATE =\n'{\\\n  \"a\": 1\\\n}';\n";
+        assert_eq!(extract_data(content).unwrap(), "{  \"a\": 1}");
+    }
+}
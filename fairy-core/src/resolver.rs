@@ -1,14 +1,26 @@
 use crate::{
-    find_nearest_external, find_nearest_package_json, module_type_from_ext,
-    package::{Exports, PACKAGE_JSON},
-    ModuleType, PackageJson, EXTENSIONS,
+    archive::ArchiveFileSystem,
+    cache::ResolverCache,
+    is_builtin, module_type_from_ext,
+    package::{Browser, BrowserTarget, Exports, PACKAGE_JSON},
+    peers::satisfies,
+    trace::{self, Explanation, TraceStep},
+    tsconfig::TSCONFIG_JSON,
+    BuiltinFallback, DuplicatePackage, FileSystem, InstallReport, Lockfile, ModuleType,
+    OsFileSystem, PackageCopy, PackageJson, PeerWarning, ResolveError, ResolveErrorKind,
+    ResolveOptions, VersionMismatch, Workspace, NODE_MODULES,
 };
+use indexmap::IndexMap;
 use pathdiff::diff_paths;
 use relative_path::{RelativePath, RelativePathBuf};
+use serde::Serialize;
 use std::{
     collections::HashMap,
+    io,
     path::{Path, PathBuf},
+    sync::Arc,
 };
+use thiserror::Error as ThisError;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Package {
@@ -17,17 +29,42 @@ pub struct Package {
     pub entry: Entry,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Entry {
     pub path: RelativePathBuf,
     pub kind: ModuleType,
+    /// The module was disabled (eg. `"fs": false` in `browser`) and should
+    /// be replaced by an empty module
+    pub empty: bool,
+    /// A node built-in module, to be left external
+    pub builtin: bool,
 }
 
 fn is_path(path: &str) -> bool {
     path.starts_with("./") || path.starts_with("../") || path.starts_with("/")
 }
 
-fn real_id<'a>(id: &'a str) -> Option<(&'a str, Option<RelativePathBuf>)> {
+/// Packages installed in more than one location
+fn duplicates(copies: &[(String, PackageCopy)]) -> Vec<DuplicatePackage> {
+    let mut packages: HashMap<&str, Vec<PackageCopy>> = HashMap::default();
+    for (name, copy) in copies {
+        packages.entry(name).or_default().push(copy.clone());
+    }
+
+    let mut duplicates = packages
+        .into_iter()
+        .filter(|(_, copies)| copies.len() > 1)
+        .map(|(name, copies)| DuplicatePackage {
+            name: name.to_string(),
+            copies,
+        })
+        .collect::<Vec<_>>();
+
+    duplicates.sort_by(|a, b| a.name.cmp(&b.name));
+    duplicates
+}
+
+fn real_id(id: &str) -> Option<(&str, Option<RelativePathBuf>)> {
     let ret = if id.starts_with("@") {
         let first = match id.find("/") {
             Some(first) => first,
@@ -58,314 +95,1880 @@ fn real_id<'a>(id: &'a str) -> Option<(&'a str, Option<RelativePathBuf>)> {
     Some(ret)
 }
 
+/// The name of the package in the directory `root`, from its place in
+/// `node_modules` (`node_modules/@scope/name`)
+fn package_name_at(root: &Path) -> String {
+    let names = root
+        .iter()
+        .rev()
+        .take_while(|name| *name != NODE_MODULES)
+        .map(|name| name.to_string_lossy())
+        .collect::<Vec<_>>();
+
+    match names.as_slice() {
+        [name, scope, ..] if scope.starts_with('@') => format!("{}/{}", scope, name),
+        _ => names
+            .first()
+            .map(|name| name.to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn empty_package(pkgjson: PackageJson, root: PathBuf, id: &str) -> Package {
+    Package {
+        pkgjson,
+        root,
+        entry: Entry {
+            path: RelativePathBuf::from(id),
+            kind: ModuleType::Esm,
+            empty: true,
+            builtin: false,
+        },
+    }
+}
+
+fn builtin_package(id: &str) -> Package {
+    Package {
+        pkgjson: PackageJson::default(),
+        root: PathBuf::default(),
+        entry: Entry {
+            path: RelativePathBuf::from(id),
+            kind: ModuleType::Commonjs,
+            empty: false,
+            builtin: true,
+        },
+    }
+}
+
+fn is_optional_peer(pkgjson: &PackageJson, peer: &str) -> bool {
+    pkgjson
+        .fields
+        .get("peerDependenciesMeta")
+        .and_then(|meta| meta.get(peer))
+        .and_then(|meta| meta.get("optional"))
+        .and_then(|optional| optional.as_bool())
+        .unwrap_or(false)
+}
+
 pub struct Resolver {
     root: PathBuf,
+    real_root: PathBuf,
+    options: ResolveOptions,
+    /// `options.extensions()`, tried on every lookup
+    extensions: Vec<String>,
+    cache: ResolverCache,
 }
 
 impl Resolver {
     pub fn new(root: PathBuf) -> Resolver {
-        Resolver { root }
+        Resolver::with_options(root, ResolveOptions::default())
+    }
+
+    pub fn with_options(root: PathBuf, options: ResolveOptions) -> Resolver {
+        Resolver::with_file_system(root, options, Arc::new(OsFileSystem))
+    }
+
+    /// Resolve through `fs`. Zip archives in it, like the packages of a
+    /// PnP install, are read as directories
+    pub fn with_file_system(
+        root: PathBuf,
+        options: ResolveOptions,
+        fs: Arc<dyn FileSystem>,
+    ) -> Resolver {
+        let fs: Arc<dyn FileSystem> = Arc::new(ArchiveFileSystem::new(fs));
+        Resolver {
+            real_root: fs.canonicalize(&root).unwrap_or_else(|_| root.clone()),
+            root,
+            extensions: options.extensions().into_iter().map(String::from).collect(),
+            options,
+            cache: ResolverCache::new(fs),
+        }
+    }
+
+    pub fn options(&self) -> &ResolveOptions {
+        &self.options
+    }
+
+    /// Extensions tried for files imported without one, without the dot
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    pub fn file_system(&self) -> &Arc<dyn FileSystem> {
+        self.cache.fs()
     }
 
     pub fn root(&self) -> &Path {
         &self.root
     }
 
-    pub fn resolve(
-        &self,
-        base: impl AsRef<RelativePath>,
-        id: &str,
-        hint: ImportHint,
-        target: TargetEnv,
-    ) -> Option<Package> {
-        if is_path(id) {
-            self.resolve_path(base.as_ref(), &RelativePath::new(id))
-        } else {
-            self.resolve_module(base.as_ref(), id, hint, target)
+    /// Drop cached filesystem state for `path` and everything below it.
+    /// Should be called when files are added, removed or changed.
+    pub fn invalidate(&self, path: impl AsRef<Path>) {
+        self.cache.invalidate(path.as_ref())
+    }
+
+    pub fn clear_cache(&self) {
+        self.cache.clear()
+    }
+
+    /// The monorepo the project is part of, if any
+    pub fn workspace(&self) -> Option<Arc<Workspace>> {
+        self.cache.workspace(&self.root)
+    }
+
+    pub fn lockfile(&self) -> Option<Arc<Lockfile>> {
+        self.cache.lockfile(&self.root)
+    }
+
+    /// The exact version of a resolved package, from its package.json or
+    /// else the lockfile
+    pub fn package_version(&self, package: &Package) -> Option<String> {
+        if let Some(version) = &package.pkgjson.version {
+            return Some(version.clone());
+        }
+
+        match self.lockfile()?.versions(&package.pkgjson.name) {
+            [version] => Some(version.clone()),
+            _ => None,
         }
     }
 
-    fn resolve_module(
-        &self,
-        base: &RelativePath,
-        id: &str,
-        hint: ImportHint,
-        target: TargetEnv,
-    ) -> Option<Package> {
-        log::debug!("resolve external: {}", id);
-        let fp_base = base.to_path(&self.root);
+    /// Check the packages installed in the node_modules of the project and
+    /// of its workspace. Walks every node_modules, so it's worth running
+    /// once rather than on every start
+    pub fn check_install(&self) -> InstallReport {
+        let copies = self.installed_copies();
 
-        let (real_id, sub_path) = match real_id(id) {
-            Some(found) => found,
-            None => {
-                log::error!("external not found");
-                return None;
-            }
-        };
+        InstallReport {
+            mismatches: self.lockfile_mismatches(&copies),
+            duplicates: duplicates(&copies),
+            peer_warnings: self.peer_warnings(&copies),
+        }
+    }
 
-        let node_modules = match find_nearest_external(&fp_base, real_id) {
-            Some(node_modules) => node_modules,
-            None => panic!("not node_modules"),
+    /// Packages that were installed in another version than the lockfile
+    /// records, eg. after switching branches without reinstalling
+    fn lockfile_mismatches(&self, copies: &[(String, PackageCopy)]) -> Vec<VersionMismatch> {
+        let lockfile = match self.lockfile() {
+            Some(lockfile) => lockfile,
+            None => return Vec::default(),
         };
 
-        log::debug!(
-            "resolved real id {} => {}, rest: {:?}",
-            id,
-            real_id,
-            sub_path
-        );
+        let mut mismatches = Vec::default();
 
-        let fp_pkg_root = node_modules.join(real_id);
-        let fp_pkg_json = fp_pkg_root.join(PACKAGE_JSON);
+        for (name, copy) in copies {
+            let (locked, installed) = match (lockfile.versions(name), &copy.version) {
+                (locked, Some(installed)) if !locked.is_empty() => (locked, installed),
+                _ => continue,
+            };
 
-        if !fp_pkg_json.exists() {
-            log::debug!("external package {} not found at {:?}", id, fp_pkg_root)
+            if !locked.contains(installed) {
+                mismatches.push(VersionMismatch {
+                    package: name.clone(),
+                    path: copy.path.clone(),
+                    installed: installed.clone(),
+                    locked: locked.to_vec(),
+                });
+            }
         }
 
-        log::debug!("found external package path: {:?}", fp_pkg_root);
+        mismatches
+    }
 
-        let pkg_json = match PackageJson::load(&fp_pkg_root) {
-            Ok(ret) => ret,
-            Err(err) => {
-                log::error!("could not load package json: {}", err);
-                return None;
-            }
-        };
+    /// Peer dependencies of installed packages that are missing, or
+    /// installed in a version outside the requested range
+    fn peer_warnings(&self, copies: &[(String, PackageCopy)]) -> Vec<PeerWarning> {
+        let mut warnings = Vec::default();
 
-        let ret = if let Some(path) = sub_path {
-            pkg_json.resolve(&fp_pkg_root, &path, hint, target)
-        } else {
-            pkg_json.resolve_default(hint, target)
-        };
+        for (name, copy) in copies {
+            let pkgjson = match self.cache.package_json(&copy.path) {
+                Ok(ret) => ret,
+                Err(_) => continue,
+            };
 
-        log::debug!("resolved external: {:?} => {:?}", fp_pkg_root, ret);
-        ret.map(|entry| Package {
-            pkgjson: pkg_json,
-            root: fp_pkg_root,
-            entry,
-        })
+            let mut peers = pkgjson.peer_dependencies.iter().collect::<Vec<_>>();
+            peers.sort();
+
+            // Peers are found from where the package really is (eg. the pnpm store)
+            let issuer = self.cache.real_path(&copy.path).join(PACKAGE_JSON);
+
+            for (peer, range) in peers {
+                let installed =
+                    self.cache
+                        .find_nearest_external(&issuer, peer)
+                        .and_then(|node_modules| {
+                            self.cache.package_json(&node_modules.join(peer)).ok()
+                        });
+
+                match installed {
+                    None if !is_optional_peer(&pkgjson, peer) => {
+                        warnings.push(PeerWarning::Missing {
+                            package: name.clone(),
+                            path: copy.path.clone(),
+                            peer: peer.clone(),
+                            range: range.clone(),
+                        })
+                    }
+                    Some(PackageJson {
+                        version: Some(installed),
+                        ..
+                    }) if satisfies(&installed, range) == Some(false) => {
+                        warnings.push(PeerWarning::Incompatible {
+                            package: name.clone(),
+                            path: copy.path.clone(),
+                            peer: peer.clone(),
+                            range: range.clone(),
+                            installed,
+                        })
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        warnings
     }
 
-    fn resolve_path(&self, base: &RelativePath, path: &RelativePath) -> Option<Package> {
-        let parent = base.parent().unwrap_or_else(|| RelativePath::new("./"));
+    /// Every package in the node_modules of the project and of its
+    /// workspace, ordered by path
+    fn installed_copies(&self) -> Vec<(String, PackageCopy)> {
+        let mut dirs = vec![self.root.join(NODE_MODULES)];
+        if let Some(workspace) = self.workspace() {
+            dirs.push(workspace.root.join(NODE_MODULES));
+            dirs.extend(
+                workspace
+                    .packages
+                    .values()
+                    .map(|root| root.join(NODE_MODULES)),
+            );
+        }
+        dirs.sort();
+        dirs.dedup();
 
-        let resolved_path = parent.join_normalized(path);
+        let mut found = HashMap::default();
+        for dir in &dirs {
+            self.find_copies(dir, &mut found);
+        }
 
-        let fp_path = resolved_path.to_path(&self.root);
+        let mut copies = found.into_values().collect::<Vec<_>>();
+        copies.sort_by(|a, b| a.1.path.cmp(&b.1.path));
+        copies
+    }
 
-        let (pkg_root, pkgjson) = match find_nearest_package_json(&fp_path) {
-            Some(ret) => ret,
-            None => {
-                log::error!("no root level package.json");
-                return None;
+    /// Every package below `node_modules`, keyed by real path so linked
+    /// packages count once
+    fn find_copies(
+        &self,
+        node_modules: &Path,
+        found: &mut HashMap<PathBuf, (String, PackageCopy)>,
+    ) {
+        for path in self.installed_packages(node_modules) {
+            let real = self.cache.real_path(&path);
+            if found.contains_key(&real) {
+                continue;
             }
-        };
 
-        let resolved_path = match diff_paths(&fp_path, &pkg_root) {
-            Some(ret) => RelativePathBuf::from_path(ret).expect("path"),
-            None => {
-                log::error!(
-                    "could not get path diff for root: {:?}, path: {:?}",
-                    pkg_root,
-                    fp_path
-                );
-                return None;
-            }
-        };
+            let pkgjson = match self.cache.package_json(&path) {
+                Ok(ret) if !ret.name.is_empty() => ret,
+                _ => continue,
+            };
 
-        if fp_path.exists() {
-            Some(Package {
-                entry: Entry {
-                    kind: module_type_from_ext(&resolved_path).unwrap_or(pkgjson.kind),
-                    path: resolved_path,
-                },
-                root: pkg_root,
-                pkgjson,
-            })
-        } else {
-            for ext in EXTENSIONS {
-                let resolved_path = resolved_path.with_extension(*ext);
-                let fp_path = resolved_path.to_path(&pkg_root);
-
-                log::trace!("trying {:?}", fp_path);
-
-                if fp_path.exists() {
-                    return Some(Package {
-                        entry: Entry {
-                            kind: module_type_from_ext(&resolved_path).unwrap_or(pkgjson.kind),
-                            path: resolved_path,
-                        },
-                        root: pkg_root,
-                        pkgjson,
-                    });
-                }
-            }
+            found.insert(
+                real,
+                (
+                    pkgjson.name,
+                    PackageCopy {
+                        path: path.clone(),
+                        version: pkgjson.version,
+                    },
+                ),
+            );
+
+            self.find_copies(&path.join(NODE_MODULES), found);
+        }
 
-            None
+        // pnpm keeps every package in a store, linked into place
+        let store = node_modules.join(".pnpm");
+        for entry in self.file_system().read_dir(&store).unwrap_or_default() {
+            self.find_copies(&entry.join(NODE_MODULES), found);
         }
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ImportHint {
-    Import,
-    Require,
-}
+    /// Package directories directly in `node_modules`, including scoped ones
+    fn installed_packages(&self, node_modules: &Path) -> Vec<PathBuf> {
+        let fs = self.file_system();
+        let mut packages = Vec::default();
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum TargetEnv {
-    Node,
-    Browser,
-}
+        for path in fs.read_dir(node_modules).unwrap_or_default() {
+            let name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) if !name.starts_with('.') => name,
+                _ => continue,
+            };
 
-impl TargetEnv {
-    fn export_fields(&self, hint: ImportHint) -> &[&'static str] {
-        match self {
-            TargetEnv::Browser => match hint {
-                ImportHint::Import => &["browser", "import", "require", "default"],
-                ImportHint::Require => &["browser", "require", "default"],
-            },
-            TargetEnv::Node => match hint {
-                ImportHint::Import => &["import", "node", "default"],
-                ImportHint::Require => &["require", "node", "default"],
-            },
+            if name.starts_with('@') {
+                packages.extend(fs.read_dir(&path).unwrap_or_default());
+            } else {
+                packages.push(path);
+            }
         }
+
+        packages.retain(|path| self.cache.is_file(&path.join(PACKAGE_JSON)));
+        packages.sort();
+        packages
     }
-}
 
-fn get_one_of<'a>(
-    obj: &'a HashMap<String, Exports>,
-    oneof: &[&'static str],
-) -> Option<&'a Exports> {
-    for o in oneof {
-        if let Some(found) = obj.get(*o) {
-            return Some(found);
+    pub fn resolve(
+        &self,
+        base: impl AsRef<RelativePath>,
+        id: &str,
+        hint: ImportHint,
+        target: TargetEnv,
+    ) -> Result<Package, ResolveError> {
+        let base = base.as_ref();
+
+        // Resolution only depends on the directory of the importer
+        let key = (
+            base.parent()
+                .unwrap_or_else(|| RelativePath::new(""))
+                .to_relative_path_buf(),
+            id.to_string(),
+            hint,
+            target,
+        );
+
+        if let Some(found) = self.cache.resolved(&key) {
+            return Ok(found);
         }
+
+        let package = self
+            .resolve_kind(base, id, hint, target)
+            .map_err(|kind| ResolveError {
+                importer: base.to_relative_path_buf(),
+                specifier: id.to_string(),
+                kind,
+            })?;
+
+        self.cache.insert_resolved(key, package.clone());
+
+        Ok(package)
     }
 
-    None
-}
-impl PackageJson {
-    fn resolve_exports(
+    /// Resolve `id` like `resolve` does, reporting each step taken. The
+    /// cache of resolved specifiers is bypassed.
+    pub fn explain(
         &self,
-        exports: &Exports,
+        base: impl AsRef<RelativePath>,
+        id: &str,
         hint: ImportHint,
-        target_env: TargetEnv,
-    ) -> Option<Entry> {
-        match exports {
-            Exports::Str(name) => {
-                let path = RelativePath::new(name.as_str());
-                let kind = module_type_from_ext(path).unwrap_or(self.kind);
-                Some(Entry {
-                    path: path.to_relative_path_buf(),
+        target: TargetEnv,
+    ) -> Explanation {
+        let base = base.as_ref();
+
+        let (ret, steps) = trace::collect(|| self.resolve_kind(base, id, hint, target));
+
+        let mut explanation = Explanation {
+            importer: base.to_relative_path_buf(),
+            specifier: id.to_string(),
+            steps,
+            root: None,
+            entry: None,
+            error: None,
+        };
+
+        match ret {
+            Ok(package) => {
+                explanation.root = Some(package.root);
+                explanation.entry = Some(package.entry);
+            }
+            Err(kind) => {
+                let err = ResolveError {
+                    importer: base.to_relative_path_buf(),
+                    specifier: id.to_string(),
                     kind,
-                })
+                };
+                explanation.error = Some(err.to_string());
             }
-            Exports::Obj(obj) => {
-                if let Some(current) = obj.get(".") {
-                    self.resolve_exports(current, hint, target_env)
-                } else {
-                    let found = match get_one_of(&obj, target_env.export_fields(hint)) {
-                        Some(found) => found,
-                        None => return None,
-                    };
-
-                    let found = match found {
-                        Exports::Str(str) => str,
-                        _ => return self.resolve_exports(found, hint, target_env),
-                    };
-
-                    log::debug!("found package {}", found);
-
-                    Some(Entry {
-                        path: found.into(),
-                        kind: module_type_from_ext(&RelativePath::new(found)).unwrap_or(self.kind),
-                    })
-                }
+        }
+
+        explanation
+    }
+
+    fn is_candidate(&self, path: &Path) -> bool {
+        let found = self.cache.is_file(path);
+        trace::record(|| TraceStep::Candidate {
+            path: path.to_path_buf(),
+            found,
+        });
+        found
+    }
+
+    fn find_file(&self, root: &Path, path: &RelativePath) -> Option<RelativePathBuf> {
+        self.find_file_visiting(root, path, &mut Vec::default())
+    }
+
+    /// `visited` are the directories entered so far, a `main` leading back
+    /// to one of them is skipped
+    fn find_file_visiting(
+        &self,
+        root: &Path,
+        path: &RelativePath,
+        visited: &mut Vec<RelativePathBuf>,
+    ) -> Option<RelativePathBuf> {
+        let fp_path = path.to_logical_path(root);
+        if self.is_candidate(&fp_path) {
+            return Some(path.to_relative_path_buf());
+        }
+
+        for ext in &self.extensions {
+            let resolved_path = path.with_extension(ext);
+            // Already tried as is
+            if resolved_path.as_str() == path.as_str() {
+                continue;
             }
+            if self.is_candidate(&resolved_path.to_logical_path(root)) {
+                return Some(resolved_path);
+            }
+        }
+
+        if self.cache.exists(&fp_path) {
+            return self.find_directory_index(root, path, visited);
         }
+
+        None
     }
 
-    fn resolve_exports_path(
+    /// The `main` of a package.json in the directory, then `index.*`
+    fn find_directory_index(
         &self,
-        exports: &Exports,
-        hint: ImportHint,
-        target_env: TargetEnv,
+        root: &Path,
         path: &RelativePath,
-    ) -> Option<Entry> {
-        match exports {
-            Exports::Str(_) => return None,
-            Exports::Obj(obj) => {
-                if let Some(exports) = obj.get(path.as_str()) {
-                    self.resolve_exports(exports, hint, target_env)
-                } else {
-                    None
+        visited: &mut Vec<RelativePathBuf>,
+    ) -> Option<RelativePathBuf> {
+        let fp_path = path.to_logical_path(root);
+
+        let dir = path.normalize();
+        if visited.contains(&dir) {
+            log::warn!("circular main in package.json at {:?}", fp_path);
+            return None;
+        }
+        visited.push(dir);
+
+        if self.cache.is_file(&fp_path.join(PACKAGE_JSON)) {
+            match self.cache.package_json(&fp_path) {
+                Ok(PackageJson {
+                    main: Some(main), ..
+                }) => {
+                    let main = path.join_normalized(main);
+                    if let Some(found) = self.find_file_visiting(root, &main, visited) {
+                        return Some(found);
+                    }
                 }
+                Ok(_) => {}
+                Err(err) => log::error!("could not load package.json in {:?}: {:?}", fp_path, err),
             }
         }
+
+        self.extensions
+            .iter()
+            .map(|ext| path.join(format!("index.{}", ext)))
+            .find(|index| self.is_candidate(&index.to_logical_path(root)))
     }
 
-    pub fn resolve_default(&self, hint: ImportHint, target_env: TargetEnv) -> Option<Entry> {
-        if let Some(exports) = &self.exports {
-            self.resolve_exports(exports, hint, target_env)
-        } else {
-            let (kind, path) = if hint == ImportHint::Require {
-                (self.kind, self.main.as_ref().map(|m| m.as_str()).unwrap())
+    /// Point `entry` at the file it refers to, following extensions and directories
+    fn find_entry(&self, root: &Path, entry: Entry) -> Result<Entry, ResolveErrorKind> {
+        if entry.empty {
+            return Ok(entry);
+        }
+
+        let path =
+            self.find_file(root, &entry.path)
+                .ok_or_else(|| ResolveErrorKind::FileNotFound {
+                    path: entry.path.to_logical_path(root),
+                    tried: self.extensions.clone(),
+                })?;
+
+        Ok(Entry {
+            kind: module_type_from_ext(&path).unwrap_or(entry.kind),
+            path,
+            empty: false,
+            builtin: false,
+        })
+    }
+
+    /// Where the package at `path` actually lives, so its own dependencies
+    /// are found next to it (pnpm, npm link). Paths inside the project are
+    /// kept relative to `root`
+    fn package_root(&self, path: PathBuf) -> PathBuf {
+        if self.options.preserve_symlinks {
+            return path;
+        }
+
+        let real = self.cache.real_path(&path);
+        match real.strip_prefix(&self.real_root) {
+            Ok(rest) => self.root.join(rest),
+            Err(_) => real,
+        }
+    }
+
+    fn load_package_json(&self, root: &Path) -> Result<PackageJson, ResolveErrorKind> {
+        trace::record(|| TraceStep::PackageJson {
+            path: root.join(PACKAGE_JSON),
+        });
+
+        self.cache.package_json(root).map_err(|error| {
+            let not_found = error
+                .root_cause()
+                .downcast_ref::<io::Error>()
+                .map(|err| err.kind() == io::ErrorKind::NotFound)
+                .unwrap_or(false);
+
+            if not_found {
+                ResolveErrorKind::PackageNotInstalled {
+                    package: package_name_at(root),
+                }
             } else {
-                match self.kind {
-                    ModuleType::Commonjs => self
-                        .module
-                        .as_ref()
-                        .map(|m| (ModuleType::Esm, m.as_str()))
-                        .unwrap_or_else(|| match self.main.as_ref() {
-                            Some(main) => (ModuleType::Commonjs, main.as_str()),
-                            None => (ModuleType::Commonjs, "./index.js"),
-                        }),
-                    ModuleType::Esm => (
-                        ModuleType::Esm,
-                        self.main.as_ref().map(|m| m.as_str()).unwrap(),
-                    ),
+                ResolveErrorKind::InvalidPackageJson {
+                    path: root.join(PACKAGE_JSON),
+                    error,
                 }
-            };
+            }
+        })
+    }
 
-            Some(Entry {
-                kind,
-                path: RelativePathBuf::from(path),
-            })
+    fn load_nearest_package_json(
+        &self,
+        path: &Path,
+    ) -> Result<(PathBuf, PackageJson), ResolveErrorKind> {
+        let root = self.cache.find_package_root(path).ok_or_else(|| {
+            ResolveErrorKind::PackageJsonNotFound {
+                path: path.to_path_buf(),
+            }
+        })?;
+        let pkgjson = self.load_package_json(&root)?;
+        Ok((root, pkgjson))
+    }
+
+    fn resolve_kind(
+        &self,
+        base: &RelativePath,
+        id: &str,
+        hint: ImportHint,
+        target: TargetEnv,
+    ) -> Result<Package, ResolveErrorKind> {
+        let aliased = match self.options.alias(id) {
+            Some(aliased) => aliased,
+            None => return self.resolve_specifier(base, id, hint, target),
+        };
+
+        log::debug!("alias {} => {}", id, aliased);
+        trace::record(|| TraceStep::Alias {
+            from: id.to_string(),
+            to: aliased.clone(),
+        });
+
+        if is_path(&aliased) {
+            // Relative to the project root
+            self.resolve_specifier(RelativePath::new(""), &aliased, hint, target)
+        } else {
+            self.resolve_specifier(base, &aliased, hint, target)
         }
     }
 
-    pub fn resolve(
+    fn resolve_specifier(
         &self,
-        root: &Path,
-        path: &RelativePath,
+        base: &RelativePath,
+        id: &str,
         hint: ImportHint,
-        target_env: TargetEnv,
-    ) -> Option<Entry> {
-        if let Some(exports) = &self.exports {
-            if let Some(ret) = self.resolve_exports_path(exports, hint, target_env, path) {
-                return Some(ret);
+        target: TargetEnv,
+    ) -> Result<Package, ResolveErrorKind> {
+        if target == TargetEnv::Browser && !is_path(id) {
+            if let Some(ret) = self.resolve_browser_module(base, id, hint) {
+                return ret;
             }
         }
 
-        if !path.to_logical_path(root).exists() {
-            for ext in EXTENSIONS {
-                let resolved_path = path.with_extension(*ext);
-                let fp_path = resolved_path.to_path(&root);
+        let ret = if is_path(id) {
+            self.resolve_path(base, RelativePath::new(id))
+        } else if id.starts_with('#') {
+            self.resolve_import(base, id, hint, target)
+        } else if target == TargetEnv::Node && is_builtin(id) {
+            log::debug!("node built-in: {}", id);
+            trace::record(|| TraceStep::Builtin {
+                module: id.to_string(),
+            });
+            Ok(builtin_package(id))
+        } else if target == TargetEnv::Browser && is_builtin(id) {
+            self.resolve_browser_builtin(base, id, hint)
+        } else if let Some(package) = self.resolve_tsconfig_paths(base, id) {
+            Ok(package)
+        } else {
+            self.resolve_module(base, id, hint, target)
+        };
 
-                if fp_path.exists() {
-                    return Some(Entry {
-                        kind: module_type_from_ext(&resolved_path).unwrap_or(self.kind),
-                        path: resolved_path,
-                    });
+        match target {
+            TargetEnv::Browser => ret.map(|package| self.resolve_browser_file(package)),
+            TargetEnv::Node => ret,
+        }
+    }
+
+    fn resolve_browser_module(
+        &self,
+        base: &RelativePath,
+        id: &str,
+        hint: ImportHint,
+    ) -> Option<Result<Package, ResolveErrorKind>> {
+        let (pkg_root, pkgjson) = self
+            .cache
+            .find_nearest_package_json(&base.to_logical_path(&self.root))?;
+
+        match pkgjson.browser_module(id)? {
+            BrowserTarget::Bool(false) => {
+                log::debug!("module {} disabled by browser field in {:?}", id, pkg_root);
+                trace::record(|| TraceStep::BrowserField {
+                    from: id.to_string(),
+                    to: None,
+                });
+                Some(Ok(empty_package(pkgjson, pkg_root, id)))
+            }
+            BrowserTarget::Str(replacement) if replacement != id => {
+                log::debug!("module {} replaced by {} (browser field)", id, replacement);
+                trace::record(|| TraceStep::BrowserField {
+                    from: id.to_string(),
+                    to: Some(replacement.clone()),
+                });
+
+                if is_path(replacement) {
+                    let pkg_base = diff_paths(pkg_root.join(PACKAGE_JSON), &self.root)?;
+                    let pkg_base = RelativePathBuf::from_path(pkg_base).ok()?;
+                    Some(self.resolve_kind(&pkg_base, replacement, hint, TargetEnv::Browser))
+                } else {
+                    Some(self.resolve_kind(base, replacement, hint, TargetEnv::Browser))
                 }
             }
-            return None;
+            _ => None,
         }
+    }
 
-        Some(Entry {
+    fn resolve_browser_builtin(
+        &self,
+        base: &RelativePath,
+        id: &str,
+        hint: ImportHint,
+    ) -> Result<Package, ResolveErrorKind> {
+        let name = id.strip_prefix("node:").unwrap_or(id);
+
+        trace::record(|| TraceStep::Builtin {
+            module: id.to_string(),
+        });
+
+        if let Some(polyfill) = self.options.builtins.polyfills.get(name) {
+            log::debug!("node built-in {} polyfilled by {}", id, polyfill);
+
+            return if is_path(polyfill) {
+                self.resolve_path(RelativePath::new(""), RelativePath::new(polyfill))
+            } else {
+                self.resolve_module(base, polyfill, hint, TargetEnv::Browser)
+            };
+        }
+
+        // Installed packages of the same name (eg. `events` or `buffer`)
+        // are polyfills too. Any failure to find one, like an undeclared
+        // dependency under PnP, leaves the fallback
+        if !id.starts_with("node:") {
+            if let Ok(package) = self.resolve_module(base, id, hint, TargetEnv::Browser) {
+                return Ok(package);
+            }
+        }
+
+        match self.options.builtins.fallback {
+            BuiltinFallback::Empty => {
+                log::debug!("node built-in {} replaced by an empty module", id);
+                Ok(empty_package(PackageJson::default(), self.root.clone(), id))
+            }
+            BuiltinFallback::Error => Err(ResolveErrorKind::NodeBuiltin {
+                module: id.to_string(),
+            }),
+        }
+    }
+
+    fn resolve_browser_file(&self, package: Package) -> Package {
+        if package.entry.empty {
+            return package;
+        }
+
+        let replacement = match package.pkgjson.browser_file(&package.entry.path) {
+            Some(BrowserTarget::Bool(false)) => {
+                log::debug!("file {} disabled by browser field", package.entry.path);
+                trace::record(|| TraceStep::BrowserField {
+                    from: package.entry.path.to_string(),
+                    to: None,
+                });
+                let id = package.entry.path.to_string();
+                return empty_package(package.pkgjson, package.root, &id);
+            }
+            Some(BrowserTarget::Str(replacement)) => RelativePath::new(replacement),
+            _ => return package,
+        };
+
+        let path = match self.find_file(&package.root, replacement) {
+            Some(path) => path,
+            None => {
+                log::error!(
+                    "browser replacement {} not found in {:?}",
+                    replacement,
+                    package.root
+                );
+                return package;
+            }
+        };
+
+        log::debug!(
+            "file {} replaced by {} (browser field)",
+            package.entry.path,
+            path
+        );
+        trace::record(|| TraceStep::BrowserField {
+            from: package.entry.path.to_string(),
+            to: Some(path.to_string()),
+        });
+
+        Package {
+            entry: Entry {
+                kind: module_type_from_ext(&path).unwrap_or(package.pkgjson.kind),
+                path,
+                empty: false,
+                builtin: false,
+            },
+            ..package
+        }
+    }
+
+    fn resolve_tsconfig_paths(&self, base: &RelativePath, id: &str) -> Option<Package> {
+        // Dependencies are not subject to the path mappings of the project
+        if base.components().any(|m| m.as_str() == NODE_MODULES) {
+            return None;
+        }
+
+        let tsconfig = self
+            .cache
+            .find_tsconfig(&base.to_logical_path(&self.root))?;
+
+        let candidates = tsconfig.candidates(id);
+        if !candidates.is_empty() {
+            trace::record(|| TraceStep::TsconfigPaths {
+                candidates: candidates.clone(),
+            });
+        }
+
+        for candidate in candidates {
+            log::trace!("trying tsconfig path {:?}", candidate);
+            if let Ok(package) = self.resolve_file(candidate) {
+                log::debug!("resolved {} with tsconfig paths", id);
+                return Some(package);
+            }
+        }
+
+        None
+    }
+
+    fn resolve_module(
+        &self,
+        base: &RelativePath,
+        id: &str,
+        hint: ImportHint,
+        target: TargetEnv,
+    ) -> Result<Package, ResolveErrorKind> {
+        log::debug!("resolve external: {}", id);
+        let fp_base = base.to_logical_path(&self.root);
+
+        let (real_id, sub_path) = real_id(id).ok_or(ResolveErrorKind::InvalidSpecifier)?;
+
+        log::debug!(
+            "resolved real id {} => {}, rest: {:?}",
+            id,
+            real_id,
+            sub_path
+        );
+
+        let workspace_root = self
+            .workspace()
+            .and_then(|workspace| workspace.package(real_id).map(Path::to_path_buf));
+
+        // Deduped packages always come from the copy installed for the project
+        let fp_issuer = if self.options.dedupe.iter().any(|name| name == real_id) {
+            trace::record(|| TraceStep::Dedupe {
+                package: real_id.to_string(),
+            });
+            self.root.join(PACKAGE_JSON)
+        } else {
+            fp_base.clone()
+        };
+
+        let fp_pkg_root = match self.resolve_self(&fp_base, real_id) {
+            Some(root) => root,
+            None => match workspace_root.clone() {
+                Some(root) => {
+                    trace::record(|| TraceStep::Workspace {
+                        package: real_id.to_string(),
+                        root: root.clone(),
+                    });
+                    root
+                }
+                None => match self.resolve_pnp(&fp_issuer, real_id)? {
+                    Some(root) => root,
+                    None => {
+                        let node_modules = self
+                            .cache
+                            .find_nearest_external(&fp_issuer, real_id)
+                            .ok_or_else(|| ResolveErrorKind::PackageNotInstalled {
+                                package: real_id.to_string(),
+                            })?;
+                        self.package_root(node_modules.join(real_id))
+                    }
+                },
+            },
+        };
+
+        log::debug!("found external package path: {:?}", fp_pkg_root);
+
+        let pkg_json = self.load_package_json(&fp_pkg_root)?;
+
+        let entry = if let Some(path) = &sub_path {
+            pkg_json.resolve(path, hint, target, &self.options)?
+        } else {
+            pkg_json.resolve_default(hint, target, &self.options)?
+        };
+
+        let mut entry = self.find_entry(&fp_pkg_root, entry)?;
+
+        if workspace_root.is_some() {
+            entry = self.source_entry(&fp_pkg_root, &pkg_json, entry, sub_path.is_none());
+        }
+
+        log::debug!("resolved external: {:?} => {:?}", fp_pkg_root, entry);
+
+        Ok(Package {
+            pkgjson: pkg_json,
+            root: fp_pkg_root,
+            entry,
+        })
+    }
+
+    /// Workspace packages are used from source: the `source` field for
+    /// the main entry, or the build output mapped from the `outDir` of the
+    /// package tsconfig back to its `rootDir`
+    fn source_entry(&self, root: &Path, pkgjson: &PackageJson, entry: Entry, main: bool) -> Entry {
+        if entry.empty {
+            return entry;
+        }
+
+        let source = pkgjson
+            .fields
+            .get("source")
+            .and_then(|source| source.as_str())
+            .filter(|_| main)
+            .and_then(|source| self.find_file(root, RelativePath::new(source)))
+            .or_else(|| {
+                let tsconfig = root.join(TSCONFIG_JSON);
+                if !self.cache.is_file(&tsconfig) {
+                    return None;
+                }
+
+                let tsconfig = self.cache.tsconfig(&tsconfig)?;
+                let out_dir = tsconfig.out_dir.as_ref()?;
+                let root_dir = tsconfig.root_dir.as_deref().unwrap_or(root);
+
+                let built = entry.path.to_logical_path(root);
+                let source = root_dir.join(built.strip_prefix(out_dir).ok()?);
+                let source = RelativePathBuf::from_path(diff_paths(source, root)?).ok()?;
+
+                self.find_file(root, &source)
+            });
+
+        match source {
+            Some(path) => {
+                log::debug!("using source {} for {}", path, entry.path);
+                Entry {
+                    kind: module_type_from_ext(&path).unwrap_or(entry.kind),
+                    path,
+                    empty: false,
+                    builtin: false,
+                }
+            }
+            None => entry,
+        }
+    }
+
+    /// A package can import itself by name, if its package.json has `exports`
+    fn resolve_self(&self, issuer: &Path, name: &str) -> Option<PathBuf> {
+        let (root, pkgjson) = self.cache.find_nearest_package_json(issuer)?;
+        if pkgjson.name != name || pkgjson.exports.is_none() {
+            return None;
+        }
+
+        log::debug!("self reference: {} => {:?}", name, root);
+        trace::record(|| TraceStep::SelfReference {
+            package: name.to_string(),
+            root: root.clone(),
+        });
+
+        Some(root)
+    }
+
+    /// Package location from a yarn Plug'n'Play manifest, if the
+    /// importer is part of one
+    fn resolve_pnp(&self, issuer: &Path, name: &str) -> Result<Option<PathBuf>, ResolveErrorKind> {
+        let manifest = match self.cache.find_pnp(issuer) {
+            Some(manifest) => manifest,
+            None => return Ok(None),
+        };
+
+        let location = manifest.resolve(issuer, name)?;
+        if let Some(location) = &location {
+            log::debug!("pnp: {} => {:?}", name, location);
+            trace::record(|| TraceStep::Pnp {
+                package: name.to_string(),
+                location: location.clone(),
+            });
+        }
+
+        Ok(location)
+    }
+
+    fn resolve_import(
+        &self,
+        base: &RelativePath,
+        id: &str,
+        hint: ImportHint,
+        target: TargetEnv,
+    ) -> Result<Package, ResolveErrorKind> {
+        log::debug!("resolve import: {}", id);
+        let fp_base = base.to_logical_path(&self.root);
+
+        let (pkg_root, pkgjson) = self.load_nearest_package_json(&fp_base)?;
+
+        let entry = pkgjson
+            .resolve_import(id, hint, target, &self.options)?
+            .ok_or_else(|| ResolveErrorKind::ImportNotDefined {
+                package: pkgjson.name.clone(),
+                import: id.to_string(),
+            })?;
+
+        if !is_path(entry.path.as_str()) {
+            log::debug!("import {} mapped to external {}", id, entry.path);
+            return self.resolve_module(base, entry.path.as_str(), hint, target);
+        }
+
+        let entry = self.find_entry(&pkg_root, entry)?;
+
+        Ok(Package {
+            pkgjson,
+            root: pkg_root,
+            entry,
+        })
+    }
+
+    fn resolve_path(
+        &self,
+        base: &RelativePath,
+        path: &RelativePath,
+    ) -> Result<Package, ResolveErrorKind> {
+        let parent = base.parent().unwrap_or_else(|| RelativePath::new("./"));
+
+        let resolved_path = parent.join_normalized(path);
+
+        self.resolve_file(resolved_path.to_logical_path(&self.root))
+    }
+
+    fn resolve_file(&self, fp_path: PathBuf) -> Result<Package, ResolveErrorKind> {
+        let (pkg_root, pkgjson) = self.load_nearest_package_json(&fp_path)?;
+
+        let resolved_path = diff_paths(&fp_path, &pkg_root)
+            .and_then(|ret| RelativePathBuf::from_path(ret).ok())
+            .ok_or_else(|| ResolveErrorKind::PathOutsidePackage {
+                path: fp_path.clone(),
+                root: pkg_root.clone(),
+            })?;
+
+        let entry = self.find_entry(
+            &pkg_root,
+            Entry {
+                path: resolved_path,
+                kind: pkgjson.kind,
+                empty: false,
+                builtin: false,
+            },
+        )?;
+
+        Ok(Package {
+            entry,
+            root: pkg_root,
+            pkgjson,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+#[error("subpath '{subpath}' is not exported by package '{name}'")]
+pub struct NotExported {
+    pub name: String,
+    pub subpath: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImportHint {
+    Import,
+    Require,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TargetEnv {
+    Node,
+    Browser,
+}
+
+impl TargetEnv {
+    pub fn export_fields(&self, hint: ImportHint) -> &[&'static str] {
+        match self {
+            TargetEnv::Browser => match hint {
+                ImportHint::Import => &["browser", "import", "require", "default"],
+                ImportHint::Require => &["browser", "require", "default"],
+            },
+            TargetEnv::Node => match hint {
+                ImportHint::Import => &["import", "node", "default"],
+                ImportHint::Require => &["require", "node", "default"],
+            },
+        }
+    }
+
+    pub fn main_fields(&self) -> &[&'static str] {
+        match self {
+            TargetEnv::Browser => &["browser", "module", "main"],
+            TargetEnv::Node => &["module", "main"],
+        }
+    }
+}
+
+fn record_conditions(conditions: &[&str]) {
+    trace::record(|| TraceStep::Conditions {
+        conditions: conditions.iter().map(|m| m.to_string()).collect(),
+    });
+}
+
+fn pattern_key_compare(a: &str, b: &str) -> std::cmp::Ordering {
+    let base_a = a.find('*').map(|idx| idx + 1).unwrap_or(a.len());
+    let base_b = b.find('*').map(|idx| idx + 1).unwrap_or(b.len());
+
+    base_b.cmp(&base_a).then_with(|| b.len().cmp(&a.len()))
+}
+
+fn match_subpath<'a, 'b>(
+    obj: &'a IndexMap<String, Exports>,
+    path: &'b str,
+) -> Option<(&'a Exports, Option<&'b str>)> {
+    if !path.contains('*') {
+        if let Some(found) = obj.get(path) {
+            return Some((found, None));
+        }
+    }
+
+    let mut best: Option<(&'a str, &'a Exports, &'b str)> = None;
+
+    for (key, target) in obj.iter() {
+        let (base, trailer) = match key.split_once('*') {
+            Some(ret) => ret,
+            None => continue,
+        };
+
+        if trailer.contains('*') {
+            log::debug!("invalid exports pattern: {}", key);
+            continue;
+        }
+
+        if path.len() < key.len() || !path.starts_with(base) || !path.ends_with(trailer) {
+            continue;
+        }
+
+        if let Some((best_key, _, _)) = best {
+            if pattern_key_compare(key, best_key) != std::cmp::Ordering::Less {
+                continue;
+            }
+        }
+
+        best = Some((key, target, &path[base.len()..path.len() - trailer.len()]));
+    }
+
+    best.map(|(_, target, pattern)| (target, Some(pattern)))
+}
+
+impl PackageJson {
+    fn not_exported(&self, subpath: &str) -> NotExported {
+        NotExported {
+            name: self.name.clone(),
+            subpath: subpath.to_string(),
+        }
+    }
+
+    fn resolve_exports(
+        &self,
+        exports: &Exports,
+        conditions: &[&str],
+    ) -> Result<Entry, NotExported> {
+        let target = match exports {
+            Exports::Obj(obj) => obj.get(".").unwrap_or(exports),
+            _ => exports,
+        };
+
+        self.resolve_exports_target(target, ".", None, conditions)?
+            .ok_or_else(|| self.not_exported("."))
+    }
+
+    fn resolve_exports_target(
+        &self,
+        exports: &Exports,
+        subpath: &str,
+        pattern: Option<&str>,
+        conditions: &[&str],
+    ) -> Result<Option<Entry>, NotExported> {
+        match exports {
+            Exports::Null => Err(self.not_exported(subpath)),
+            Exports::Str(name) => {
+                let found = match pattern {
+                    Some(pattern) => name.replace('*', pattern),
+                    None => name.clone(),
+                };
+
+                log::debug!("found package {}", found);
+
+                Ok(Some(Entry {
+                    kind: module_type_from_ext(RelativePath::new(&found)).unwrap_or(self.kind),
+                    path: found.into(),
+                    empty: false,
+                    builtin: false,
+                }))
+            }
+            Exports::Array(list) => {
+                let mut error = None;
+
+                for item in list {
+                    if let Exports::Str(name) = item {
+                        // Only `imports` may fall back to bare specifiers
+                        if !name.starts_with("./") && !subpath.starts_with('#') {
+                            log::debug!("skipping invalid exports fallback: {}", name);
+                            continue;
+                        }
+                    }
+
+                    // Like invalid targets, excluded ones fall through to the next entry
+                    match self.resolve_exports_target(item, subpath, pattern, conditions) {
+                        Ok(Some(found)) => return Ok(Some(found)),
+                        Ok(None) => {}
+                        Err(err) => {
+                            error.get_or_insert(err);
+                        }
+                    }
+                }
+
+                match error {
+                    Some(err) => Err(err),
+                    None => Ok(None),
+                }
+            }
+            // The first key, in package.json order, that is one of the
+            // conditions and resolves to something
+            Exports::Obj(obj) => {
+                for (key, target) in obj {
+                    if !conditions.contains(&key.as_str()) {
+                        continue;
+                    }
+
+                    if let Some(found) =
+                        self.resolve_exports_target(target, subpath, pattern, conditions)?
+                    {
+                        return Ok(Some(found));
+                    }
+                }
+
+                Ok(None)
+            }
+        }
+    }
+
+    fn resolve_exports_path(
+        &self,
+        exports: &Exports,
+        conditions: &[&str],
+        path: &RelativePath,
+    ) -> Option<Result<Entry, NotExported>> {
+        match exports {
+            Exports::Obj(obj) => {
+                let (target, pattern) = match_subpath(obj, path.as_str())?;
+                let ret = self
+                    .resolve_exports_target(target, path.as_str(), pattern, conditions)
+                    .and_then(|found| found.ok_or_else(|| self.not_exported(path.as_str())));
+                Some(ret)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn resolve_import(
+        &self,
+        id: &str,
+        hint: ImportHint,
+        target_env: TargetEnv,
+        options: &ResolveOptions,
+    ) -> Result<Option<Entry>, NotExported> {
+        let imports = match &self.imports {
+            Some(imports) => imports,
+            None => return Ok(None),
+        };
+
+        let (target, pattern) = match match_subpath(imports, id) {
+            Some(ret) => ret,
+            None => return Ok(None),
+        };
+
+        let conditions = options.conditions(target_env, hint);
+        record_conditions(&conditions);
+
+        self.resolve_exports_target(target, id, pattern, &conditions)?
+            .ok_or_else(|| self.not_exported(id))
+            .map(Some)
+    }
+
+    pub fn browser_module(&self, id: &str) -> Option<&BrowserTarget> {
+        match &self.browser {
+            Some(Browser::Obj(map)) => map.get(id),
+            _ => None,
+        }
+    }
+
+    pub fn browser_file(&self, path: &RelativePath) -> Option<&BrowserTarget> {
+        let map = match &self.browser {
+            Some(Browser::Obj(map)) => map,
+            _ => return None,
+        };
+
+        let path = path.normalize();
+
+        // Keys may leave out the extension of the file they replace
+        map.iter()
+            .filter(|(key, _)| is_path(key))
+            .find(|(key, _)| {
+                let key = RelativePath::new(key).normalize();
+                key == path || (key.extension().is_none() && key == path.with_extension(""))
+            })
+            .map(|(_, target)| target)
+    }
+
+    fn main_field(&self, field: &str) -> Option<(&str, ModuleType)> {
+        match field {
+            "main" => self.main.as_deref().map(|main| (main, self.kind)),
+            "module" => self
+                .module
+                .as_deref()
+                .map(|module| (module, ModuleType::Esm)),
+            "browser" => match &self.browser {
+                Some(Browser::Str(browser)) => Some((browser.as_str(), self.kind)),
+                _ => None,
+            },
+            field => self
+                .fields
+                .get(field)
+                .and_then(|value| value.as_str())
+                .map(|value| (value, self.kind)),
+        }
+    }
+
+    pub fn resolve_default(
+        &self,
+        hint: ImportHint,
+        target_env: TargetEnv,
+        options: &ResolveOptions,
+    ) -> Result<Entry, NotExported> {
+        if let Some(exports) = &self.exports {
+            let conditions = options.conditions(target_env, hint);
+            record_conditions(&conditions);
+            return self.resolve_exports(exports, &conditions);
+        }
+
+        for field in options.main_fields(target_env) {
+            // `module` points at esm, which cannot be required
+            if hint == ImportHint::Require && field == "module" {
+                continue;
+            }
+
+            let value = self.main_field(field);
+            trace::record(|| TraceStep::MainField {
+                field: field.to_string(),
+                value: value.map(|(path, _)| path.to_string()),
+            });
+
+            if let Some((path, kind)) = value {
+                let path = RelativePathBuf::from(path);
+                return Ok(Entry {
+                    kind: module_type_from_ext(&path).unwrap_or(kind),
+                    path,
+                    empty: false,
+                    builtin: false,
+                });
+            }
+        }
+
+        Ok(Entry {
+            kind: self.kind,
+            path: RelativePathBuf::from("./index.js"),
+            empty: false,
+            builtin: false,
+        })
+    }
+
+    /// Entry for a subpath of the package. Paths not covered by `exports`
+    /// are returned as is, the resolver locates the actual file
+    pub fn resolve(
+        &self,
+        path: &RelativePath,
+        hint: ImportHint,
+        target_env: TargetEnv,
+        options: &ResolveOptions,
+    ) -> Result<Entry, NotExported> {
+        if let Some(exports) = &self.exports {
+            let conditions = options.conditions(target_env, hint);
+            record_conditions(&conditions);
+            // A matched key (including a `null` exclusion) is final
+            if let Some(ret) = self.resolve_exports_path(exports, &conditions, path) {
+                return ret;
+            }
+        }
+
+        Ok(Entry {
             kind: module_type_from_ext(path).unwrap_or(self.kind),
             path: path.to_relative_path_buf(),
+            empty: false,
+            builtin: false,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryFileSystem;
+
+    fn package(json: &str) -> PackageJson {
+        serde_json::from_str(json).expect("package.json")
+    }
+
+    fn resolve(pkgjson: &PackageJson, path: &str) -> Result<String, NotExported> {
+        pkgjson
+            .resolve(
+                RelativePath::new(path),
+                ImportHint::Import,
+                TargetEnv::Browser,
+                &ResolveOptions::default(),
+            )
+            .map(|entry| entry.path.to_string())
+    }
+
+    #[test]
+    fn exports_subpath_patterns() {
+        let pkgjson = package(
+            r#"{
+                "name": "lib",
+                "exports": {
+                    "./features/*.js": "./src/features/*.js",
+                    "./features/internal/*": null,
+                    "./utils/*": { "import": "./esm/utils/*.mjs" },
+                    "./*": "./dist/*"
+                }
+            }"#,
+        );
+
+        assert_eq!(
+            resolve(&pkgjson, "./features/a/b.js").unwrap(),
+            "./src/features/a/b.js"
+        );
+        assert_eq!(
+            resolve(&pkgjson, "./utils/fmt").unwrap(),
+            "./esm/utils/fmt.mjs"
+        );
+        assert_eq!(resolve(&pkgjson, "./other.js").unwrap(), "./dist/other.js");
+        assert!(resolve(&pkgjson, "./features/internal/x").is_err());
+    }
+
+    #[test]
+    fn exports_nested_conditions() {
+        let pkgjson = package(
+            r#"{
+                "name": "lib",
+                "exports": {
+                    ".": { "browser": { "worker": "./w.js" }, "default": "./d.js" },
+                    "./ordered": { "default": "./default.js", "browser": "./browser.js" },
+                    "./nested": {
+                        "node": "./node.js",
+                        "browser": { "require": "./b.cjs", "import": "./b.mjs" }
+                    }
+                }
+            }"#,
+        );
+
+        assert_eq!(resolve(&pkgjson, ".").unwrap(), "./d.js");
+        // Keys are matched in the order of the package.json
+        assert_eq!(resolve(&pkgjson, "./ordered").unwrap(), "./default.js");
+        assert_eq!(resolve(&pkgjson, "./nested").unwrap(), "./b.cjs");
+    }
+
+    #[test]
+    fn exports_array_fallbacks() {
+        let pkgjson = package(
+            r#"{
+                "name": "lib",
+                "exports": {
+                    ".": [null, "lib/invalid.js", { "node": "./node.js" }, "./index.js"],
+                    "./excluded": [null],
+                    "./none": [{ "node": "./node.js" }]
+                }
+            }"#,
+        );
+
+        let entry = pkgjson
+            .resolve_default(
+                ImportHint::Import,
+                TargetEnv::Browser,
+                &ResolveOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(entry.path, "./index.js");
+
+        assert!(resolve(&pkgjson, "./excluded").is_err());
+        assert!(resolve(&pkgjson, "./none").is_err());
+    }
+
+    fn resolve_entry(resolver: &Resolver, id: &str) -> String {
+        let package = resolver
+            .resolve("src/main.js", id, ImportHint::Import, TargetEnv::Browser)
+            .unwrap_or_else(|err| panic!("{}: {}", id, err));
+        let path = package.entry.path.to_logical_path(&package.root);
+        diff_paths(path, resolver.root())
+            .expect("relative path")
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+
+    fn assert_directories(resolver: &Resolver) {
+        assert_eq!(
+            resolve_entry(resolver, "./components"),
+            "src/components/index.tsx"
+        );
+        assert_eq!(resolve_entry(resolver, "./lib"), "src/lib/lib.js");
+        assert_eq!(
+            resolve_entry(resolver, "dir-main"),
+            "node_modules/dir-main/dist/index.js"
+        );
+        assert_eq!(
+            resolve_entry(resolver, "dir-main/utils"),
+            "node_modules/dir-main/utils/index.js"
+        );
+        assert_eq!(
+            resolve_entry(resolver, "nested-main"),
+            "node_modules/nested-main/lib/entry.js"
+        );
+    }
+
+    #[test]
+    fn directories() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/directories");
+        assert_directories(&Resolver::new(root.canonicalize().unwrap()));
+    }
+
+    #[test]
+    fn directories_in_memory() {
+        let fs = MemoryFileSystem::new()
+            .with_file("/app/package.json", r#"{ "name": "directories" }"#)
+            .with_file("/app/src/main.js", "")
+            .with_file("/app/src/components/index.tsx", "")
+            .with_file("/app/src/lib/package.json", r#"{ "main": "./lib.js" }"#)
+            .with_file("/app/src/lib/lib.js", "")
+            .with_file(
+                "/app/node_modules/dir-main/package.json",
+                r#"{ "name": "dir-main", "main": "./dist" }"#,
+            )
+            .with_file("/app/node_modules/dir-main/dist/index.js", "")
+            .with_file("/app/node_modules/dir-main/utils/index.js", "")
+            .with_file(
+                "/app/node_modules/nested-main/package.json",
+                r#"{ "name": "nested-main", "main": "lib" }"#,
+            )
+            .with_file(
+                "/app/node_modules/nested-main/lib/package.json",
+                r#"{ "main": "entry.js" }"#,
+            )
+            .with_file("/app/node_modules/nested-main/lib/entry.js", "");
+
+        assert_directories(&Resolver::with_file_system(
+            PathBuf::from("/app"),
+            ResolveOptions::default(),
+            Arc::new(fs),
+        ));
+    }
+
+    #[test]
+    fn check_install() {
+        let fs = MemoryFileSystem::new()
+            .with_file("/app/package.json", r#"{ "name": "app" }"#)
+            .with_file(
+                "/app/yarn.lock",
+                concat!(
+                    "lib@^1.0.0:\n  version \"1.0.0\"\n\n",
+                    "react@^17.0.0:\n  version \"17.0.2\"\n\n",
+                    "react@^18.0.0:\n  version \"18.2.0\"\n",
+                ),
+            )
+            .with_file(
+                "/app/node_modules/lib/package.json",
+                r#"{
+                    "name": "lib",
+                    "version": "1.1.0",
+                    "peerDependencies": { "missing": "^1.0.0", "react": "^17.0.0" }
+                }"#,
+            )
+            .with_file(
+                "/app/node_modules/react/package.json",
+                r#"{ "name": "react", "version": "18.2.0" }"#,
+            )
+            .with_file(
+                "/app/node_modules/other/package.json",
+                r#"{ "name": "other", "version": "1.0.0" }"#,
+            )
+            .with_file(
+                "/app/node_modules/other/node_modules/react/package.json",
+                r#"{ "name": "react", "version": "17.0.2" }"#,
+            )
+            .with_file(
+                "/app/node_modules/lib/node_modules/other/package.json",
+                r#"{ "name": "other", "version": "1.0.0" }"#,
+            );
+
+        let resolver = Resolver::with_file_system(
+            PathBuf::from("/app"),
+            ResolveOptions::default(),
+            Arc::new(fs),
+        );
+
+        let report = resolver.check_install();
+
+        assert_eq!(
+            report.mismatches,
+            [VersionMismatch {
+                package: "lib".into(),
+                path: "/app/node_modules/lib".into(),
+                installed: "1.1.0".into(),
+                locked: vec!["1.0.0".into()],
+            }]
+        );
+
+        assert_eq!(report.duplicates.len(), 2);
+        // The same version, installed twice
+        assert_eq!(report.duplicates[0].name, "other");
+        assert_eq!(report.duplicates[0].copies.len(), 2);
+        assert!(!report.duplicates[0].has_multiple_versions());
+        assert_eq!(report.duplicates[1].name, "react");
+        assert!(report.duplicates[1].has_multiple_versions());
+
+        assert_eq!(
+            report.peer_warnings,
+            [
+                PeerWarning::Missing {
+                    package: "lib".into(),
+                    path: "/app/node_modules/lib".into(),
+                    peer: "missing".into(),
+                    range: "^1.0.0".into(),
+                },
+                PeerWarning::Incompatible {
+                    package: "lib".into(),
+                    path: "/app/node_modules/lib".into(),
+                    peer: "react".into(),
+                    range: "^17.0.0".into(),
+                    installed: "18.2.0".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn circular_directory_main() {
+        let fs = MemoryFileSystem::new()
+            .with_file("/app/package.json", r#"{ "name": "app" }"#)
+            .with_file("/app/src/main.js", "")
+            .with_file("/app/src/a/package.json", r#"{ "main": "../b" }"#)
+            .with_file("/app/src/b/package.json", r#"{ "main": "../a" }"#)
+            .with_file("/app/src/c/package.json", r#"{ "main": "./" }"#)
+            .with_file("/app/src/c/index.js", "");
+        let resolver = Resolver::with_file_system(
+            PathBuf::from("/app"),
+            ResolveOptions::default(),
+            Arc::new(fs),
+        );
+
+        for id in ["./a", "./b"] {
+            assert!(resolver
+                .resolve("src/main.js", id, ImportHint::Import, TargetEnv::Browser)
+                .is_err());
+        }
+        assert_eq!(resolve_entry(&resolver, "./c"), "src/c/index.js");
+    }
+
+    #[test]
+    fn files_created_after_a_failed_lookup() {
+        let fs = Arc::new(
+            MemoryFileSystem::new()
+                .with_file("/app/package.json", r#"{ "name": "app" }"#)
+                .with_file("/app/src/main.js", ""),
+        );
+        let resolver = Resolver::with_file_system(
+            PathBuf::from("/app"),
+            ResolveOptions::default(),
+            fs.clone(),
+        );
+
+        for id in ["./later", "lib"] {
+            assert!(resolver
+                .resolve("src/main.js", id, ImportHint::Import, TargetEnv::Browser)
+                .is_err());
+        }
+
+        fs.insert("/app/src/later.js", "");
+        fs.insert(
+            "/app/node_modules/lib/package.json",
+            r#"{ "name": "lib", "main": "lib.js" }"#,
+        );
+        fs.insert("/app/node_modules/lib/lib.js", "");
+
+        assert_eq!(resolve_entry(&resolver, "./later"), "src/later.js");
+        assert_eq!(resolve_entry(&resolver, "lib"), "node_modules/lib/lib.js");
+    }
+
+    #[test]
+    fn invalidate() {
+        let fs = Arc::new(
+            MemoryFileSystem::new()
+                .with_file("/app/package.json", r#"{ "name": "app" }"#)
+                .with_file("/app/src/main.js", "")
+                .with_file("/app/src/util.js", "")
+                .with_file(
+                    "/app/node_modules/lib/package.json",
+                    r#"{ "name": "lib", "main": "a.js" }"#,
+                )
+                .with_file("/app/node_modules/lib/a.js", "")
+                .with_file("/app/node_modules/lib/b.js", ""),
+        );
+        let resolver = Resolver::with_file_system(
+            PathBuf::from("/app"),
+            ResolveOptions::default(),
+            fs.clone(),
+        );
+
+        assert_eq!(resolve_entry(&resolver, "./util"), "src/util.js");
+        assert_eq!(resolve_entry(&resolver, "lib"), "node_modules/lib/a.js");
+
+        fs.remove("/app/src/util.js");
+        fs.insert(
+            "/app/node_modules/lib/package.json",
+            r#"{ "name": "lib", "main": "b.js" }"#,
+        );
+
+        // Found files stay cached until invalidated
+        assert_eq!(resolve_entry(&resolver, "./util"), "src/util.js");
+
+        resolver.invalidate("/app/src/util.js");
+        resolver.invalidate("/app/node_modules/lib/package.json");
+
+        assert!(resolver
+            .resolve(
+                "src/main.js",
+                "./util",
+                ImportHint::Import,
+                TargetEnv::Browser
+            )
+            .is_err());
+        assert_eq!(resolve_entry(&resolver, "lib"), "node_modules/lib/b.js");
+    }
+
+    #[test]
+    fn imports() {
+        let fs = MemoryFileSystem::new()
+            .with_file(
+                "/app/package.json",
+                r##"{
+                    "name": "app",
+                    "imports": {
+                        "#config": "./src/config.js",
+                        "#utils/*": "./src/utils/*.js",
+                        "#env": { "node": "./src/env.node.js", "default": "./src/env.js" },
+                        "#missing": "./src/missing.js"
+                    }
+                }"##,
+            )
+            .with_file("/app/src/main.js", "")
+            .with_file("/app/src/config.js", "")
+            .with_file("/app/src/utils/date.js", "")
+            .with_file("/app/src/env.js", "")
+            .with_file("/app/src/env.node.js", "");
+        let resolver = Resolver::with_file_system(
+            PathBuf::from("/app"),
+            ResolveOptions::default(),
+            Arc::new(fs),
+        );
+
+        assert_eq!(resolve_entry(&resolver, "#config"), "src/config.js");
+        assert_eq!(resolve_entry(&resolver, "#utils/date"), "src/utils/date.js");
+        assert_eq!(resolve_entry(&resolver, "#env"), "src/env.js");
+
+        let node = resolver
+            .resolve("src/main.js", "#env", ImportHint::Import, TargetEnv::Node)
+            .unwrap();
+        assert_eq!(node.entry.path.normalize(), "src/env.node.js");
+
+        for (id, missing) in [
+            ("#missing", "/app/src/missing.js"),
+            ("#utils/time", "/app/src/utils/time.js"),
+        ] {
+            let err = resolver
+                .resolve("src/main.js", id, ImportHint::Import, TargetEnv::Browser)
+                .unwrap_err();
+            assert!(
+                matches!(&err.kind, ResolveErrorKind::FileNotFound { path, .. } if path == Path::new(missing)),
+                "{}: {:?}",
+                id,
+                err.kind
+            );
+        }
+
+        let err = resolver
+            .resolve(
+                "src/main.js",
+                "#other",
+                ImportHint::Import,
+                TargetEnv::Browser,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ResolveErrorKind::ImportNotDefined { .. }
+        ));
+    }
+
+    #[test]
+    fn package_without_package_json() {
+        let fs = MemoryFileSystem::new()
+            .with_file("/app/package.json", r#"{ "name": "app" }"#)
+            .with_file("/app/src/main.js", "")
+            .with_file("/app/node_modules/partial/index.js", "")
+            .with_file("/app/node_modules/@scope/partial/index.js", "")
+            .with_file("/app/node_modules/invalid/package.json", "{");
+        let resolver = Resolver::with_file_system(
+            PathBuf::from("/app"),
+            ResolveOptions::default(),
+            Arc::new(fs),
+        );
+        let resolve = |id| {
+            resolver
+                .resolve("src/main.js", id, ImportHint::Import, TargetEnv::Browser)
+                .unwrap_err()
+                .kind
+        };
+
+        for id in ["partial", "@scope/partial"] {
+            assert!(
+                matches!(&resolve(id), ResolveErrorKind::PackageNotInstalled { package } if package == id),
+                "{}",
+                id
+            );
+        }
+        assert!(matches!(
+            resolve("invalid"),
+            ResolveErrorKind::InvalidPackageJson { .. }
+        ));
+    }
+
+    fn pnp_project() -> MemoryFileSystem {
+        MemoryFileSystem::new()
+            .with_file("/app/package.json", r#"{ "name": "app" }"#)
+            .with_file("/app/main.js", "")
+            .with_file("/app/polyfills/path.js", "")
+            .with_file(
+                "/app/.pnp.data.json",
+                r#"{
+                    "packageRegistryData": [
+                        [null, [[null, { "packageLocation": "./", "packageDependencies": [] }]]]
+                    ]
+                }"#,
+            )
+    }
+
+    #[test]
+    fn browser_builtins_under_pnp() {
+        let resolve = |options: ResolveOptions| {
+            Resolver::with_file_system(PathBuf::from("/app"), options, Arc::new(pnp_project()))
+                .resolve("main.js", "path", ImportHint::Import, TargetEnv::Browser)
+        };
+
+        let err = resolve(ResolveOptions::default()).unwrap_err();
+        assert!(matches!(err.kind, ResolveErrorKind::NodeBuiltin { .. }));
+
+        let mut options = ResolveOptions::default();
+        options.builtins.fallback = BuiltinFallback::Empty;
+        assert!(resolve(options).unwrap().entry.empty);
+
+        let mut options = ResolveOptions::default();
+        options
+            .builtins
+            .polyfills
+            .insert("path".into(), "./polyfills/path.js".into());
+        assert_eq!(resolve(options).unwrap().entry.path, "polyfills/path.js");
+    }
+
+    #[test]
+    fn installed_packages_polyfill_builtins() {
+        let fs = MemoryFileSystem::new()
+            .with_file("/app/package.json", r#"{ "name": "app" }"#)
+            .with_file("/app/main.js", "")
+            .with_file(
+                "/app/node_modules/events/package.json",
+                r#"{ "name": "events", "main": "events.js" }"#,
+            )
+            .with_file("/app/node_modules/events/events.js", "");
+        let resolver = Resolver::with_file_system(
+            PathBuf::from("/app"),
+            ResolveOptions::default(),
+            Arc::new(fs),
+        );
+
+        assert_eq!(
+            resolve_entry(&resolver, "events"),
+            "node_modules/events/events.js"
+        );
+        assert!(resolver
+            .resolve(
+                "main.js",
+                "node:events",
+                ImportHint::Import,
+                TargetEnv::Browser
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn exports_patterns_prefer_longest_prefix() {
+        assert_eq!(
+            pattern_key_compare("./a/b/*", "./a/*"),
+            std::cmp::Ordering::Less
+        );
+        assert_eq!(
+            pattern_key_compare("./a/*.js", "./a/*"),
+            std::cmp::Ordering::Less
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use crate::resolver::Entry;
+use relative_path::RelativePathBuf;
+use serde::Serialize;
+use std::{cell::RefCell, fmt, path::PathBuf};
+
+thread_local! {
+    static TRACE: RefCell<Option<Vec<TraceStep>>> = const { RefCell::new(None) };
+}
+
+/// A single decision made while resolving a specifier
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum TraceStep {
+    Alias {
+        from: String,
+        to: String,
+    },
+    /// Replaced by the `browser` field. `None` when disabled
+    BrowserField {
+        from: String,
+        to: Option<String>,
+    },
+    TsconfigPaths {
+        candidates: Vec<PathBuf>,
+    },
+    /// The importer's own package, imported by name
+    SelfReference {
+        package: String,
+        root: PathBuf,
+    },
+    Workspace {
+        package: String,
+        root: PathBuf,
+    },
+    Builtin {
+        module: String,
+    },
+    /// Resolved from the project root, see `ResolveOptions::dedupe`
+    Dedupe {
+        package: String,
+    },
+    Pnp {
+        package: String,
+        location: PathBuf,
+    },
+    NodeModules {
+        path: PathBuf,
+        found: bool,
+    },
+    PackageJson {
+        path: PathBuf,
+    },
+    Conditions {
+        conditions: Vec<String>,
+    },
+    MainField {
+        field: String,
+        value: Option<String>,
+    },
+    Candidate {
+        path: PathBuf,
+        found: bool,
+    },
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = |found: &bool| if *found { "found" } else { "not found" };
+
+        match self {
+            TraceStep::Alias { from, to } => write!(f, "alias {} => {}", from, to),
+            TraceStep::BrowserField { from, to: Some(to) } => {
+                write!(f, "browser field {} => {}", from, to)
+            }
+            TraceStep::BrowserField { from, to: None } => {
+                write!(f, "browser field {} => disabled", from)
+            }
+            TraceStep::TsconfigPaths { candidates } => {
+                write!(f, "tsconfig paths {:?}", candidates)
+            }
+            TraceStep::SelfReference { package, root } => {
+                write!(f, "self reference {} at {:?}", package, root)
+            }
+            TraceStep::Workspace { package, root } => {
+                write!(f, "workspace package {} at {:?}", package, root)
+            }
+            TraceStep::Builtin { module } => write!(f, "node built-in {}", module),
+            TraceStep::Dedupe { package } => write!(f, "dedupe {}", package),
+            TraceStep::Pnp { package, location } => write!(f, "pnp {} at {:?}", package, location),
+            TraceStep::NodeModules { path, found } => {
+                write!(f, "node_modules {:?} ({})", path, status(found))
+            }
+            TraceStep::PackageJson { path } => write!(f, "package.json {:?}", path),
+            TraceStep::Conditions { conditions } => {
+                write!(f, "export conditions [{}]", conditions.join(", "))
+            }
+            TraceStep::MainField { field, value } => match value {
+                Some(value) => write!(f, "field {}: {}", field, value),
+                None => write!(f, "field {}: -", field),
+            },
+            TraceStep::Candidate { path, found } => {
+                write!(f, "candidate {:?} ({})", path, status(found))
+            }
+        }
+    }
+}
+
+/// The steps taken to resolve a specifier, see `Resolver::explain`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Explanation {
+    pub importer: RelativePathBuf,
+    pub specifier: String,
+    pub steps: Vec<TraceStep>,
+    pub root: Option<PathBuf>,
+    pub entry: Option<Entry>,
+    pub error: Option<String>,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "'{}' from '{}'", self.specifier, self.importer)?;
+
+        for step in &self.steps {
+            writeln!(f, "  {}", step)?;
+        }
+
+        match (&self.root, &self.entry, &self.error) {
+            (_, Some(entry), _) if entry.empty => write!(f, "=> empty module"),
+            (Some(root), Some(entry), _) => write!(
+                f,
+                "=> {:?} ({:?})",
+                entry.path.to_logical_path(root),
+                entry.kind
+            ),
+            (_, _, Some(error)) => write!(f, "=> {}", error),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Record a step, if a trace is being collected on this thread
+pub(crate) fn record(step: impl FnOnce() -> TraceStep) {
+    TRACE.with(|trace| {
+        if let Some(steps) = trace.borrow_mut().as_mut() {
+            steps.push(step());
+        }
+    })
+}
+
+/// Run `func`, collecting the steps it records
+pub(crate) fn collect<R>(func: impl FnOnce() -> R) -> (R, Vec<TraceStep>) {
+    let prev = TRACE.with(|trace| trace.borrow_mut().replace(Vec::default()));
+    let ret = func();
+    let steps = TRACE.with(|trace| std::mem::replace(&mut *trace.borrow_mut(), prev));
+    (ret, steps.unwrap_or_default())
+}
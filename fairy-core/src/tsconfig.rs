@@ -0,0 +1,435 @@
+use crate::{find_nearest_package, FileSystem};
+use anyhow::{bail, Context};
+use relative_path::RelativePath;
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+pub static TSCONFIG_JSON: &str = "tsconfig.json";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CompilerOptions {
+    base_url: Option<String>,
+    paths: Option<HashMap<String, Vec<String>>>,
+    root_dir: Option<String>,
+    out_dir: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Extends {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TsConfigJson {
+    extends: Option<Extends>,
+    #[serde(default)]
+    compiler_options: CompilerOptions,
+}
+
+/// The path options of a tsconfig.json, with its `extends` chain applied
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TsConfig {
+    pub base_url: Option<PathBuf>,
+    pub paths: HashMap<String, Vec<String>>,
+    /// Directory of the config that declared `paths`
+    pub paths_dir: PathBuf,
+    pub root_dir: Option<PathBuf>,
+    pub out_dir: Option<PathBuf>,
+}
+
+impl TsConfig {
+    pub fn load(fs: &dyn FileSystem, path: &Path) -> anyhow::Result<TsConfig> {
+        let mut seen = HashSet::default();
+        TsConfig::load_inner(fs, path, &mut seen)
+    }
+
+    fn load_inner(
+        fs: &dyn FileSystem,
+        path: &Path,
+        seen: &mut HashSet<PathBuf>,
+    ) -> anyhow::Result<TsConfig> {
+        if !seen.insert(path.to_path_buf()) {
+            bail!("circular extends in {:?}", path);
+        }
+
+        let data = fs
+            .read_to_string(path)
+            .context(format!("at path: {:?}", path))?;
+        let json: TsConfigJson = serde_json::from_str(&strip_jsonc(&data))
+            .context(format!("invalid tsconfig: {:?}", path))?;
+
+        let dir = path.parent().unwrap_or_else(|| Path::new("/"));
+
+        let extends = match json.extends {
+            Some(Extends::One(one)) => vec![one],
+            Some(Extends::Many(many)) => many,
+            None => Vec::default(),
+        };
+
+        let mut config = TsConfig {
+            paths_dir: dir.to_path_buf(),
+            ..Default::default()
+        };
+
+        // Later configs override earlier ones
+        for extend in extends {
+            let parent = find_extends(fs, dir, &extend)
+                .with_context(|| format!("could not find {} extended by {:?}", extend, path))?;
+            let parent = TsConfig::load_inner(fs, &parent, seen)?;
+
+            if parent.base_url.is_some() {
+                config.base_url = parent.base_url;
+            }
+
+            if parent.root_dir.is_some() {
+                config.root_dir = parent.root_dir;
+            }
+
+            if parent.out_dir.is_some() {
+                config.out_dir = parent.out_dir;
+            }
+
+            if !parent.paths.is_empty() {
+                config.paths = parent.paths;
+                config.paths_dir = parent.paths_dir;
+            }
+        }
+
+        if let Some(base_url) = json.compiler_options.base_url {
+            config.base_url = Some(RelativePath::new(&base_url).to_logical_path(dir));
+        }
+
+        if let Some(root_dir) = json.compiler_options.root_dir {
+            config.root_dir = Some(RelativePath::new(&root_dir).to_logical_path(dir));
+        }
+
+        if let Some(out_dir) = json.compiler_options.out_dir {
+            config.out_dir = Some(RelativePath::new(&out_dir).to_logical_path(dir));
+        }
+
+        if let Some(paths) = json.compiler_options.paths {
+            config.paths = paths;
+            config.paths_dir = dir.to_path_buf();
+        }
+
+        Ok(config)
+    }
+
+    /// Directory the targets of `paths` are relative to
+    pub fn paths_base(&self) -> &Path {
+        self.base_url.as_deref().unwrap_or(&self.paths_dir)
+    }
+
+    fn match_paths<'a, 'b>(&'a self, id: &'b str) -> Option<(&'a Vec<String>, Option<&'b str>)> {
+        if let Some(targets) = self.paths.get(id) {
+            return Some((targets, None));
+        }
+
+        let mut best: Option<(usize, &'a Vec<String>, &'b str)> = None;
+
+        for (key, targets) in self.paths.iter() {
+            let (prefix, suffix) = match key.split_once('*') {
+                Some(ret) => ret,
+                None => continue,
+            };
+
+            if id.len() < prefix.len() + suffix.len()
+                || !id.starts_with(prefix)
+                || !id.ends_with(suffix)
+            {
+                continue;
+            }
+
+            if let Some((len, _, _)) = best {
+                if len >= prefix.len() {
+                    continue;
+                }
+            }
+
+            best = Some((
+                prefix.len(),
+                targets,
+                &id[prefix.len()..id.len() - suffix.len()],
+            ));
+        }
+
+        best.map(|(_, targets, star)| (targets, Some(star)))
+    }
+
+    /// Candidate paths for a bare specifier, in the order they should be tried
+    pub fn candidates(&self, id: &str) -> Vec<PathBuf> {
+        let mut candidates = Vec::default();
+
+        if let Some((targets, star)) = self.match_paths(id) {
+            let base = self.paths_base();
+            for target in targets {
+                let target = match star {
+                    Some(star) => target.replace('*', star),
+                    None => target.clone(),
+                };
+                candidates.push(RelativePath::new(&target).to_logical_path(base));
+            }
+        }
+
+        if let Some(base_url) = &self.base_url {
+            candidates.push(RelativePath::new(id).to_logical_path(base_url));
+        }
+
+        candidates
+    }
+}
+
+fn find_extends(fs: &dyn FileSystem, dir: &Path, extend: &str) -> Option<PathBuf> {
+    let path = if extend.starts_with('.') || extend.starts_with('/') {
+        RelativePath::new(extend).to_logical_path(dir)
+    } else {
+        find_nearest_package(fs, dir, extend)?
+    };
+
+    if fs.is_dir(&path) {
+        return Some(path.join(TSCONFIG_JSON)).filter(|m| fs.is_file(m));
+    }
+
+    if fs.is_file(&path) {
+        return Some(path);
+    }
+
+    // Appended, `tsconfig.base` is `tsconfig.base.json`
+    let mut path = path.into_os_string();
+    path.push(".json");
+    let path = PathBuf::from(path);
+    if fs.is_file(&path) {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// Remove comments and trailing commas, which tsconfig files allow
+fn strip_jsonc(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            output.push(c);
+            match c {
+                '\\' => {
+                    if let Some(next) = chars.next() {
+                        output.push(next);
+                    }
+                }
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                output.push(c);
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        output.push(next);
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for next in chars.by_ref() {
+                    if prev == '*' && next == '/' {
+                        break;
+                    }
+                    prev = next;
+                }
+            }
+            '}' | ']' => {
+                let trimmed = output.trim_end().len();
+                if output[..trimmed].ends_with(',') {
+                    output.truncate(trimmed - 1);
+                }
+                output.push(c);
+            }
+            _ => output.push(c),
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryFileSystem;
+
+    #[test]
+    fn jsonc() {
+        let input = r#"{
+            // line comment
+            "a": "http://example.com", /* block */
+            "b": "/* not a comment */",
+            "c": "quote \" // still a string",
+            "d": [1, 2,],
+            /* multi
+               line */
+            "e": { "f": 1, },
+        }"#;
+
+        let value: serde_json::Value = serde_json::from_str(&strip_jsonc(input)).expect("json");
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "a": "http://example.com",
+                "b": "/* not a comment */",
+                "c": "quote \" // still a string",
+                "d": [1, 2],
+                "e": { "f": 1 },
+            })
+        );
+    }
+
+    fn load(fs: &MemoryFileSystem, path: &str) -> anyhow::Result<TsConfig> {
+        TsConfig::load(fs, Path::new(path))
+    }
+
+    #[test]
+    fn extends() {
+        let fs = MemoryFileSystem::new()
+            .with_file(
+                "/app/tsconfig.json",
+                r#"{
+                    "extends": ["@tsconfig/base", "./config/tsconfig.paths"],
+                    "compilerOptions": { "outDir": "./dist" }
+                }"#,
+            )
+            .with_file(
+                "/app/config/tsconfig.paths.json",
+                r#"{
+                    "extends": "@tsconfig/strict/tsconfig.json",
+                    "compilerOptions": { "paths": { "@/*": ["./src/*"] } }
+                }"#,
+            )
+            .with_file(
+                "/app/node_modules/@tsconfig/base/tsconfig.json",
+                r#"{ "compilerOptions": { "baseUrl": ".", "rootDir": "./src", "outDir": "./out" } }"#,
+            )
+            .with_file(
+                "/app/node_modules/@tsconfig/strict/tsconfig.json",
+                r#"{ "compilerOptions": { "rootDir": "./lib" } }"#,
+            );
+
+        let config = load(&fs, "/app/tsconfig.json").expect("tsconfig");
+        assert_eq!(
+            config.base_url.as_deref(),
+            Some(Path::new("/app/node_modules/@tsconfig/base"))
+        );
+        // The later `extends` wins
+        assert_eq!(
+            config.root_dir.as_deref(),
+            Some(Path::new("/app/node_modules/@tsconfig/strict/lib"))
+        );
+        assert_eq!(config.out_dir.as_deref(), Some(Path::new("/app/dist")));
+        assert_eq!(config.paths["@/*"], ["./src/*"]);
+        assert_eq!(config.paths_dir, Path::new("/app/config"));
+
+        assert!(load(&fs, "/app/missing/tsconfig.json").is_err());
+    }
+
+    #[test]
+    fn extends_errors() {
+        let fs = MemoryFileSystem::new()
+            .with_file("/app/tsconfig.json", r#"{ "extends": "./tsconfig.base" }"#)
+            .with_file(
+                "/app/tsconfig.base.json",
+                r#"{ "extends": "./tsconfig.json" }"#,
+            )
+            .with_file("/app/self.json", r#"{ "extends": "./self.json" }"#)
+            .with_file("/app/missing.json", r#"{ "extends": "not-installed" }"#);
+
+        for path in ["/app/tsconfig.json", "/app/self.json"] {
+            let err = load(&fs, path).unwrap_err();
+            assert!(
+                format!("{:#}", err).contains("circular extends"),
+                "{}",
+                path
+            );
+        }
+
+        let err = load(&fs, "/app/missing.json").unwrap_err();
+        assert!(format!("{:#}", err).contains("could not find not-installed"));
+    }
+
+    #[test]
+    fn paths() {
+        let fs = MemoryFileSystem::new().with_file(
+            "/app/tsconfig.json",
+            r#"{
+                "compilerOptions": {
+                    "baseUrl": "./src",
+                    "paths": {
+                        "config": ["./config/index.ts"],
+                        "@/*": ["./*", "./generated/*"],
+                        "@/components/*": ["./ui/*.tsx"],
+                        "*.css": ["./styles/*.css"]
+                    }
+                }
+            }"#,
+        );
+        let config = load(&fs, "/app/tsconfig.json").expect("tsconfig");
+        let candidates = |id| {
+            config
+                .candidates(id)
+                .into_iter()
+                .map(|path| path.to_string_lossy().replace('\\', "/"))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            candidates("config"),
+            ["/app/src/config/index.ts", "/app/src/config"]
+        );
+        assert_eq!(
+            candidates("@/utils/date"),
+            [
+                "/app/src/utils/date",
+                "/app/src/generated/utils/date",
+                "/app/src/@/utils/date",
+            ]
+        );
+        // The longest prefix wins
+        assert_eq!(
+            candidates("@/components/button"),
+            ["/app/src/ui/button.tsx", "/app/src/@/components/button"]
+        );
+        assert_eq!(
+            candidates("theme.css"),
+            ["/app/src/styles/theme.css", "/app/src/theme.css"]
+        );
+        assert_eq!(candidates("react"), ["/app/src/react"]);
+
+        let config = TsConfig {
+            paths: config.paths.clone(),
+            paths_dir: PathBuf::from("/app"),
+            ..TsConfig::default()
+        };
+        // Without `baseUrl`, targets are relative to the config
+        assert_eq!(
+            config.candidates("@/a"),
+            [Path::new("/app/a"), Path::new("/app/generated/a")]
+        );
+        assert!(config.candidates("react").is_empty());
+    }
+}
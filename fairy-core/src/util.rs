@@ -1,17 +1,80 @@
 use relative_path::RelativePath;
 
-use crate::{package::PACKAGE_JSON, ModuleType, PackageJson};
+use crate::{package::PACKAGE_JSON, FileSystem, ModuleType};
 use std::path::{Path, PathBuf};
 
-pub static NODE_MODULES: &'static str = "node_modules";
-
-pub static EXTENSIONS: &'static [&'static str] = &["js", "jsx", "mjs", "ts", "tsx", "cjs", "mjs"];
+pub static NODE_MODULES: &str = "node_modules";
+
+/// Extensions tried, in order, for specifiers without one. Overridden by
+/// `ResolveOptions::extensions`
+pub static EXTENSIONS: &[&str] = &["js", "jsx", "mjs", "ts", "tsx", "cjs", "mts", "cts", "json"];
+
+pub static BUILTIN_MODULES: &[&str] = &[
+    "assert",
+    "assert/strict",
+    "async_hooks",
+    "buffer",
+    "child_process",
+    "cluster",
+    "console",
+    "constants",
+    "crypto",
+    "dgram",
+    "diagnostics_channel",
+    "dns",
+    "dns/promises",
+    "domain",
+    "events",
+    "fs",
+    "fs/promises",
+    "http",
+    "http2",
+    "https",
+    "inspector",
+    "module",
+    "net",
+    "os",
+    "path",
+    "path/posix",
+    "path/win32",
+    "perf_hooks",
+    "process",
+    "punycode",
+    "querystring",
+    "readline",
+    "readline/promises",
+    "repl",
+    "stream",
+    "stream/consumers",
+    "stream/promises",
+    "stream/web",
+    "string_decoder",
+    "sys",
+    "timers",
+    "timers/promises",
+    "tls",
+    "trace_events",
+    "tty",
+    "url",
+    "util",
+    "util/types",
+    "v8",
+    "vm",
+    "wasi",
+    "worker_threads",
+    "zlib",
+];
+
+/// Whether `id` names a node built-in module, eg. `fs` or `node:fs`
+pub fn is_builtin(id: &str) -> bool {
+    id.starts_with("node:") || BUILTIN_MODULES.contains(&id)
+}
 
-pub fn find_package_root(path: &Path) -> Option<PathBuf> {
+pub fn find_package_root(fs: &dyn FileSystem, path: &Path) -> Option<PathBuf> {
     let mut parent = path.parent();
     while let Some(p) = parent {
         let pkg = p.join(PACKAGE_JSON);
-        if pkg.is_file() {
+        if fs.is_file(&pkg) {
             return Some(p.to_path_buf());
         }
         parent = p.parent();
@@ -19,53 +82,12 @@ pub fn find_package_root(path: &Path) -> Option<PathBuf> {
     None
 }
 
-pub fn find_nearest_package_json(path: &Path) -> Option<(PathBuf, PackageJson)> {
-    match find_package_root(path) {
-        Some(root) => PackageJson::load(&root).map(|pkg| (root, pkg)).ok(),
-        None => None,
-    }
-}
-
-pub fn find_nearest_node_modules(path: &Path) -> Option<PathBuf> {
-    let mut parent = Some(path);
-
-    while let Some(path) = parent {
-        let node_modules_path = path.join(NODE_MODULES);
-        if node_modules_path.exists() {
-            return Some(node_modules_path);
-        }
-
-        parent = path.parent();
-    }
-
-    None
-}
-
-pub fn find_nearest_external(path: &Path, id: &str) -> Option<PathBuf> {
-    let mut parent = Some(path);
-
-    while let Some(path) = parent {
-        let node_modules_path = path.join(NODE_MODULES);
-        if node_modules_path.exists() {
-            if node_modules_path.join(id).exists() {
-                return Some(node_modules_path);
-            } else {
-                // pri //ntln!("not found {:?} {:?}", path, node_modules_path.join(id));
-            }
-        }
-
-        parent = path.parent();
-    }
-
-    None
-}
-
-pub fn find_nearest_package(path: &Path, package: &str) -> Option<PathBuf> {
+pub fn find_nearest_package(fs: &dyn FileSystem, path: &Path, package: &str) -> Option<PathBuf> {
     let mut parent = Some(path);
 
     while let Some(path) = parent {
         let node_modules_path = path.join(NODE_MODULES).join(package);
-        if node_modules_path.exists() {
+        if fs.exists(&node_modules_path) {
             return Some(node_modules_path);
         }
 
@@ -77,8 +99,8 @@ pub fn find_nearest_package(path: &Path, package: &str) -> Option<PathBuf> {
 
 pub fn module_type_from_ext(path: &RelativePath) -> Option<ModuleType> {
     match path.extension() {
-        Some("cjs") => Some(ModuleType::Commonjs),
-        Some("mjs") => Some(ModuleType::Esm),
+        Some("cjs") | Some("cts") => Some(ModuleType::Commonjs),
+        Some("mjs") | Some("mts") => Some(ModuleType::Esm),
         _ => None,
     }
 }
@@ -0,0 +1,137 @@
+use crate::{package::PACKAGE_JSON, FileSystem, PackageJson, NODE_MODULES};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// The packages of a monorepo, as declared by `workspaces` in its root package.json
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Workspace {
+    pub root: PathBuf,
+    /// Package name => package root
+    pub packages: HashMap<String, PathBuf>,
+}
+
+impl Workspace {
+    /// Find the workspace `path` is part of
+    pub fn find(fs: &dyn FileSystem, path: &Path) -> Option<Workspace> {
+        let mut parent = Some(path);
+        while let Some(p) = parent {
+            if fs.is_file(&p.join(PACKAGE_JSON)) {
+                match PackageJson::load(fs, p) {
+                    Ok(pkgjson) if pkgjson.workspaces.is_some() => {
+                        return Some(Workspace::load(fs, p, &pkgjson))
+                    }
+                    Ok(_) => {}
+                    Err(err) => log::error!("could not load package.json in {:?}: {:?}", p, err),
+                }
+            }
+            parent = p.parent();
+        }
+        None
+    }
+
+    pub fn load(fs: &dyn FileSystem, root: &Path, pkgjson: &PackageJson) -> Workspace {
+        let patterns = match &pkgjson.workspaces {
+            Some(workspaces) => workspaces.patterns(),
+            None => &[],
+        };
+
+        let mut dirs = Vec::default();
+        let mut excluded = Vec::default();
+
+        for pattern in patterns {
+            match pattern.strip_prefix('!') {
+                Some(pattern) => expand(fs, root, pattern, &mut excluded),
+                None => expand(fs, root, pattern, &mut dirs),
+            }
+        }
+
+        let mut packages = HashMap::default();
+
+        for dir in dirs {
+            if excluded.contains(&dir) || !fs.is_file(&dir.join(PACKAGE_JSON)) {
+                continue;
+            }
+
+            match PackageJson::load(fs, &dir) {
+                Ok(pkgjson) if !pkgjson.name.is_empty() => {
+                    packages.insert(pkgjson.name, dir);
+                }
+                Ok(_) => log::warn!("workspace package {:?} has no name", dir),
+                Err(err) => log::error!("could not load package.json in {:?}: {:?}", dir, err),
+            }
+        }
+
+        Workspace {
+            root: root.to_path_buf(),
+            packages,
+        }
+    }
+
+    pub fn package(&self, name: &str) -> Option<&Path> {
+        self.packages.get(name).map(|root| root.as_path())
+    }
+
+    /// The package containing `path`
+    pub fn package_at(&self, path: &Path) -> Option<(&str, &Path)> {
+        self.packages
+            .iter()
+            .filter(|(_, root)| path.starts_with(root))
+            .max_by_key(|(_, root)| root.as_os_str().len())
+            .map(|(name, root)| (name.as_str(), root.as_path()))
+    }
+}
+
+/// Expand a workspace glob. Supports `*` and `**` as whole path segments
+fn expand(fs: &dyn FileSystem, dir: &Path, pattern: &str, output: &mut Vec<PathBuf>) {
+    let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+
+    let (segment, rest) = match pattern.split_once('/') {
+        Some((segment, rest)) => (segment, Some(rest)),
+        None => (pattern, None),
+    };
+
+    let mut matched = |path: PathBuf| match rest {
+        Some(rest) => expand(fs, &path, rest, output),
+        None => output.push(path),
+    };
+
+    match segment {
+        "" => output.push(dir.to_path_buf()),
+        "*" => subdirs(fs, dir).into_iter().for_each(matched),
+        "**" => {
+            let rest = rest.unwrap_or("");
+            expand(fs, dir, rest, output);
+            for sub in subdirs(fs, dir) {
+                expand(fs, &sub, pattern, output);
+            }
+        }
+        segment => {
+            let path = dir.join(segment);
+            if fs.is_dir(&path) {
+                matched(path)
+            }
+        }
+    }
+}
+
+fn subdirs(fs: &dyn FileSystem, dir: &Path) -> Vec<PathBuf> {
+    let entries = match fs.read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::default(),
+    };
+
+    let mut dirs = entries
+        .into_iter()
+        .filter(|path| {
+            path.file_name()
+                .map(|name| name != NODE_MODULES)
+                .unwrap_or(false)
+        })
+        .filter(|path| fs.is_dir(path))
+        .collect::<Vec<_>>();
+
+    dirs.sort();
+    dirs
+}
@@ -1,6 +1,6 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
-use fairy_core::Config;
+use fairy_core::{Config, OsFileSystem};
 use fairy_dev::{create_resolver, Resolver};
 use relative_path::RelativePathBuf;
 
@@ -21,7 +21,11 @@ fn test_node_resolver() -> anyhow::Result<()> {
 }
 
 fn tesst_resolver() -> anyhow::Result<()> {
-    let resolver = Resolver::new(PathBuf::from("fairy-http/examples/frontend").canonicalize()?);
+    let resolver = Resolver::new(
+        PathBuf::from("fairy-http/examples/frontend").canonicalize()?,
+        Default::default(),
+        Arc::new(OsFileSystem),
+    );
 
     let found = resolver.resolve(
         &FileName::Real("fairy-http/examples/frontend/src/main.tsx".into()),
@@ -44,6 +48,8 @@ fn main() -> anyhow::Result<()> {
         entry: RelativePathBuf::from("./src/main.tsx"),
         env,
         plugins: Vec::default(),
+        resolve: Default::default(),
+        check_install: true,
     };
 
     // test_node_resolver()?;
@@ -1,13 +1,12 @@
 use crate::{
-    loader::{FileLoader, Loader},
+    loader::{FileLoader, Loader, WORKSPACE_PREFIX},
     resolver::Resolver,
     transformers::{
-        AssetsTransform, Externals as ExternalTransform, ImportTransform, ImportTransformer,
-        ImportTransportFold,
+        AssetsTransform, Externals as ExternalTransform, FileLoaderTransform, ImportTransform,
+        ImportTransformer, ImportTransportFold,
     },
 };
-use anyhow::bail;
-use fairy_core::Package;
+use fairy_core::{FileLoader as FilePlugin, FileSystem, Package, ResolveOptions, Workspace};
 use pathdiff::diff_paths;
 use relative_path::RelativePath;
 use std::{
@@ -15,7 +14,7 @@ use std::{
     sync::Arc,
 };
 use swc::{
-    config::{JscConfig, Options, TransformConfig},
+    config::{JscConfig, Options, SourceMapsConfig, TransformConfig},
     TransformOutput,
 };
 use swc_atoms::{js_word, JsWord};
@@ -43,12 +42,22 @@ pub struct Compiler {
     globals: Globals,
     resolver: Lrc<Resolver>,
     env: Lrc<AHashMap<JsWord, Expr>>,
+    loaders: Vec<Arc<dyn FilePlugin>>,
     pub(crate) transformer: ImportTransform,
 }
 
 impl Compiler {
-    pub fn new(root: PathBuf, env: Lrc<AHashMap<JsWord, Expr>>) -> Compiler {
-        let file_loader = FileLoader::new(root.clone());
+    pub fn new(
+        root: PathBuf,
+        env: Lrc<AHashMap<JsWord, Expr>>,
+        resolve: ResolveOptions,
+        fs: Arc<dyn FileSystem>,
+        loaders: Vec<Arc<dyn FilePlugin>>,
+    ) -> Compiler {
+        let resolver = Arc::new(Resolver::new(root.clone(), resolve, fs));
+
+        // Through the resolver, which also reads from zip archives
+        let file_loader = FileLoader::new(root.clone(), resolver.file_system().clone());
 
         let cm = Lrc::new(SourceMap::with_file_loader(
             Box::new(file_loader),
@@ -59,10 +68,11 @@ impl Compiler {
         let handler = Handler::with_tty_emitter(ColorConfig::Auto, true, false, None);
         let globals = Globals::default();
 
-        let resolver = Resolver::new(root.clone());
-
+        // File loaders go first, before bare specifiers are made external
         let plugins = vec![
-            Box::new(ExternalTransform::new()) as Box<dyn ImportTransformer + Send + Sync>,
+            Box::new(FileLoaderTransform::new(resolver.clone(), loaders.clone()))
+                as Box<dyn ImportTransformer + Send + Sync>,
+            Box::new(ExternalTransform::new(resolver.clone())),
             Box::new(AssetsTransform::new()),
         ];
 
@@ -74,8 +84,9 @@ impl Compiler {
             compiler,
             handler: Lrc::new(handler),
             globals,
-            resolver: Arc::new(resolver),
+            resolver,
             env,
+            loaders,
             transformer,
         }
     }
@@ -88,18 +99,47 @@ impl Compiler {
         &self.root
     }
 
+    pub fn resolver(&self) -> &Lrc<Resolver> {
+        &self.resolver
+    }
+
+    pub fn file_system(&self) -> &Arc<dyn FileSystem> {
+        self.resolver.file_system()
+    }
+
+    pub fn loaders(&self) -> &[Arc<dyn FilePlugin>] {
+        &self.loaders
+    }
+
+    /// Invalidate cached resolutions after `path` changed on disk
+    pub fn invalidate(&self, path: impl AsRef<Path>) {
+        self.resolver.invalidate(path.as_ref())
+    }
+
+    pub fn workspace(&self) -> Option<Arc<Workspace>> {
+        self.resolver.workspace()
+    }
+
     pub fn resolve(&self, name: &str) -> anyhow::Result<Package> {
-        match self.resolver.resolve_external(name) {
-            Some(ret) => Ok(ret),
-            None => bail!("could not resolve"),
-        }
+        Ok(self.resolver.resolve_external(name)?)
+    }
+
+    pub fn package_version(&self, package: &Package) -> Option<String> {
+        self.resolver.package_version(package)
     }
 
     pub fn create_bundler<'a>(
         &'a self,
         config: swc_bundler::Config,
     ) -> swc_bundler::Bundler<'a, Loader, Lrc<Resolver>> {
-        let loader = Loader::new(self.cm.clone(), self.env.clone(), self.handler.clone());
+        let loader = Loader::new(
+            self.cm.clone(),
+            self.env.clone(),
+            self.handler.clone(),
+            self.root.clone(),
+            self.file_system().clone(),
+            self.loaders.clone(),
+        );
 
         let bundler = Bundler::new(
             &self.globals,
@@ -113,6 +153,8 @@ impl Compiler {
         bundler
     }
 
+    /// Compile the module at `path`. The output includes a source map with
+    /// the original sources
     pub fn compile(&self, path: impl AsRef<Path>) -> anyhow::Result<TransformOutput> {
         let file = self.cm.load_file(path.as_ref())?;
 
@@ -147,8 +189,12 @@ impl Compiler {
                             .into(),
                             ..Default::default()
                         },
+                        inline_sources_content: true.into(),
                         ..Default::default()
                     },
+                    source_maps: Some(SourceMapsConfig::Bool(true)),
+                    // Devtools list the original under the url the module is served at
+                    source_file_name: Some(self.url(path.as_ref(), rel_path)),
                     ..Default::default()
                 },
                 |_, _| {
@@ -167,6 +213,24 @@ impl Compiler {
         Ok(out)
     }
 
+    /// The url the module at `path` is served at. Workspace packages
+    /// outside the root are served under `WORKSPACE_PREFIX`
+    fn url(&self, path: &Path, rel_path: &RelativePath) -> String {
+        if !path.starts_with(&self.root) {
+            let package = self.workspace().and_then(|workspace| {
+                let (name, root) = workspace.package_at(path)?;
+                let rel_path = RelativePath::from_path(path.strip_prefix(root).ok()?).ok()?;
+                Some(format!("{}{}/{}", WORKSPACE_PREFIX, name, rel_path))
+            });
+
+            if let Some(url) = package {
+                return url;
+            }
+        }
+
+        format!("/{}", rel_path)
+    }
+
     pub fn run<F, R>(&self, func: F) -> R
     where
         F: FnOnce() -> R,
@@ -1,13 +1,23 @@
 use crate::{
-    externals::Externals, loader::NODE_MODULES_PREFIX, transformers::EXTENSIONS, Compiler, Content,
-    Error,
+    externals::Externals,
+    loader::{FILE_LOADER_PREFIX, NODE_MODULES_PREFIX, WORKSPACE_PREFIX},
+    locket::Locket,
+    Compiler, Content, Error,
 };
+use fairy_core::{FileLoader, FileSystem, FileType};
+use locking::LockApi;
 use relative_path::RelativePath;
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 pub struct Payload {
     pub mime: mime::Mime,
     pub content: Content,
+    /// Url of the source map of a compiled module
+    pub source_map: Option<String>,
 }
 
 #[cfg(feature = "concurrent")]
@@ -29,40 +39,75 @@ impl ContentLoader for ContentLoaderBox {
 pub struct ScriptLoader {
     compiler: Compiler,
     externals: Externals,
+    /// The extensions of the resolver. JSON is served as is, or as module
+    /// by a `JsonLoader`
+    extensions: Vec<String>,
+    /// Source maps of the served modules, by path
+    source_maps: Locket<HashMap<PathBuf, String>>,
 }
 
 impl ScriptLoader {
     pub fn new(compiler: Compiler) -> ScriptLoader {
+        let extensions = compiler
+            .resolver()
+            .extensions()
+            .iter()
+            .filter(|ext| *ext != "json")
+            .cloned()
+            .collect();
+
         ScriptLoader {
             compiler,
             externals: Externals::default(),
+            extensions,
+            source_maps: Default::default(),
         }
     }
 
     pub fn resolve(&self, path: &RelativePath) -> Option<PathBuf> {
-        if let Some(ext) = path.extension() {
-            if !EXTENSIONS.contains(&ext) {
-                return None;
-            }
+        if path.starts_with(WORKSPACE_PREFIX) {
+            return self.resolve_workspace(path);
         }
 
-        let fp = path.to_path(self.compiler.root());
-        if fp.exists() {
-            let meta = match fp.metadata() {
-                Ok(ret) => ret,
-                Err(_) => return None,
-            };
+        self.resolve_in(self.compiler.root(), path)
+    }
+
+    /// Map `<WORKSPACE_PREFIX><package name>/<path>` onto the package sources
+    fn resolve_workspace(&self, path: &RelativePath) -> Option<PathBuf> {
+        let rest = path
+            .as_str()
+            .trim_start_matches('/')
+            .strip_prefix(WORKSPACE_PREFIX.trim_start_matches('/'))?;
+        let workspace = self.compiler.workspace()?;
+
+        let (name, root) = workspace.packages.iter().find(|(name, _)| {
+            rest.strip_prefix(name.as_str())
+                .map(|rest| rest.starts_with('/'))
+                .unwrap_or(false)
+        })?;
 
-            if meta.is_dir() {
+        self.resolve_in(root, RelativePath::new(&rest[name.len()..]))
+    }
+
+    fn resolve_in(&self, root: &Path, path: &RelativePath) -> Option<PathBuf> {
+        if let Some(ext) = path.extension() {
+            if !self.extensions.iter().any(|m| m == ext) {
                 return None;
             }
+        }
+
+        let fs = self.compiler.file_system();
 
-            return Some(fp);
+        let fp = path.to_path(root);
+        match fs.file_type(&fp) {
+            Ok(FileType::File) => return Some(fp),
+            Ok(FileType::Dir) => return None,
+            Err(_) => {}
         }
 
-        for ext in EXTENSIONS {
-            let fp = path.with_extension(*ext).to_path(self.compiler.root());
-            if fp.exists() {
+        for ext in &self.extensions {
+            let fp = path.with_extension(ext).to_path(root);
+            if fs.exists(&fp) {
                 return Some(fp);
             }
         }
@@ -71,8 +116,50 @@ impl ScriptLoader {
     }
 }
 
+impl ScriptLoader {
+    /// The source map of the module at `path`, without `.map`
+    fn load_source_map(&self, path: &RelativePath) -> Result<Payload, Error> {
+        let full_path = match self.resolve(path) {
+            Some(path) => path,
+            None => return Err(Error::NotFound),
+        };
+
+        let cached = self.source_maps.read().get(&full_path).cloned();
+
+        // Modules served before a restart are compiled again
+        let map = match cached {
+            Some(map) => map,
+            None => match self.compiler.compile(&full_path)?.map {
+                Some(map) => {
+                    self.source_maps.write().insert(full_path, map.clone());
+                    map
+                }
+                None => return Err(Error::NotFound),
+            },
+        };
+
+        Ok(Payload {
+            mime: mime::APPLICATION_JSON,
+            content: Content::new(map.into_bytes()),
+            source_map: None,
+        })
+    }
+}
+
 impl ContentLoader for ScriptLoader {
     fn load(&self, path: &RelativePath) -> Result<Payload, Error> {
+        if let Some(module) = path.as_str().strip_suffix(".map") {
+            if !module.starts_with(NODE_MODULES_PREFIX) {
+                match self.load_source_map(RelativePath::new(module)) {
+                    // Not a compiled module, maybe a map on disk
+                    Err(Error::NotFound) => {}
+                    ret => return ret,
+                }
+            }
+        }
+
+        let mut source_map = None;
+
         let content = if path.starts_with(NODE_MODULES_PREFIX) {
             let file_name = path.to_string().replace(NODE_MODULES_PREFIX, "");
 
@@ -90,23 +177,31 @@ impl ContentLoader for ScriptLoader {
                 None => return Err(Error::NotFound),
             };
 
-            Content::new(self.compiler.compile(full_path)?.code.into_bytes())
+            let output = self.compiler.compile(&full_path)?;
+            if let Some(map) = output.map {
+                source_map = Some(format!("/{}.map", path.as_str().trim_start_matches('/')));
+                self.source_maps.write().insert(full_path, map);
+            }
+
+            Content::new(output.code.into_bytes())
         };
 
         Ok(Payload {
             mime: mime::APPLICATION_JAVASCRIPT,
             content,
+            source_map,
         })
     }
 }
 
 pub struct AssetLoader {
     root: PathBuf,
+    fs: Arc<dyn FileSystem>,
 }
 
 impl AssetLoader {
-    pub fn new(root: PathBuf) -> AssetLoader {
-        AssetLoader { root }
+    pub fn new(root: PathBuf, fs: Arc<dyn FileSystem>) -> AssetLoader {
+        AssetLoader { root, fs }
     }
 }
 
@@ -119,22 +214,120 @@ impl ContentLoader for AssetLoader {
 
         let fp = path.to_path(&self.root);
 
-        if !fp.exists() {
+        if !self.fs.is_file(&fp) {
             return Err(Error::NotFound);
         }
 
-        let meta = fp.metadata()?;
+        let bytes = self.fs.read(&fp)?;
+
+        let mime = mime_guess::from_ext(ext).first_or_octet_stream();
+        Ok(Payload {
+            mime,
+            content: Content::new(bytes),
+            source_map: None,
+        })
+    }
+}
+
+/// Serves the modules made by `FileLoader`s, under `FILE_LOADER_PREFIX`
+pub struct PluginLoader {
+    root: PathBuf,
+    fs: Arc<dyn FileSystem>,
+    loaders: Vec<Arc<dyn FileLoader>>,
+}
+
+impl PluginLoader {
+    pub fn new(
+        root: PathBuf,
+        fs: Arc<dyn FileSystem>,
+        loaders: Vec<Arc<dyn FileLoader>>,
+    ) -> PluginLoader {
+        PluginLoader { root, fs, loaders }
+    }
+}
+
+impl ContentLoader for PluginLoader {
+    fn load(&self, path: &RelativePath) -> Result<Payload, Error> {
+        let path = match path.as_str().strip_prefix(FILE_LOADER_PREFIX) {
+            Some(path) => RelativePath::new(path),
+            None => return Err(Error::NotFound),
+        };
+
+        let loader = match self.loaders.iter().find(|loader| loader.matches(path)) {
+            Some(loader) => loader,
+            None => return Err(Error::NotFound),
+        };
+
+        let fp = path.to_path(&self.root);
 
-        if meta.is_dir() {
+        if !self.fs.is_file(&fp) {
             return Err(Error::NotFound);
         }
 
-        let bytes = std::fs::read(fp)?;
+        let module = loader.load(path, self.fs.read(&fp)?)?;
 
-        let mime = mime_guess::from_ext(ext).first_or_octet_stream();
         Ok(Payload {
-            mime,
-            content: Content::new(bytes),
+            mime: mime::APPLICATION_JAVASCRIPT,
+            content: Content::new(module.into_bytes()),
+            source_map: None,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fairy_core::{CssLoader, JsonLoader, MemoryFileSystem};
+
+    fn plugin_loader() -> PluginLoader {
+        let fs = MemoryFileSystem::new()
+            .with_file("/app/src/data.json", r#"{"name":"fairy"}"#)
+            .with_file("/app/src/style.css", "a {}")
+            .with_file("/app/src/logo.png", "png");
+
+        let loaders: Vec<Arc<dyn FileLoader>> = vec![
+            Arc::new(JsonLoader::default()),
+            Arc::new(CssLoader::default()),
+        ];
+
+        PluginLoader::new(PathBuf::from("/app"), Arc::new(fs), loaders)
+    }
+
+    fn load(loader: &PluginLoader, path: &str) -> Result<String, Error> {
+        let payload = loader.load(RelativePath::new(path))?;
+        assert_eq!(payload.mime, mime::APPLICATION_JAVASCRIPT);
+        Ok(payload.content.to_string().expect("utf-8"))
+    }
+
+    #[test]
+    fn plugin_loader_serves_modules() {
+        let loader = plugin_loader();
+
+        let json = load(&loader, "/node_modules/.fairy-loader/src/data.json").expect("json");
+        assert!(json.contains("export default json;"));
+
+        let css = load(&loader, "/node_modules/.fairy-loader/src/style.css").expect("css");
+        assert!(css.contains("\"/src/style.css\""));
+    }
+
+    #[test]
+    fn plugin_loader_not_found() {
+        let loader = plugin_loader();
+
+        // Outside the prefix
+        assert!(matches!(
+            load(&loader, "/src/data.json"),
+            Err(Error::NotFound)
+        ));
+        // No loader for the file
+        assert!(matches!(
+            load(&loader, "/node_modules/.fairy-loader/src/logo.png"),
+            Err(Error::NotFound)
+        ));
+        // Missing file
+        assert!(matches!(
+            load(&loader, "/node_modules/.fairy-loader/src/missing.json"),
+            Err(Error::NotFound)
+        ));
+    }
+}
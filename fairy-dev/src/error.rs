@@ -10,4 +10,6 @@ pub enum Error {
     Compiler(#[from] anyhow::Error),
     #[error("io error: {0}")]
     Io(#[from] io::Error),
+    #[error("loader error: {0}")]
+    Load(#[from] fairy_core::LoadError),
 }
@@ -2,29 +2,40 @@ use std::collections::HashMap;
 
 use locking::LockApi;
 
-use crate::{bundler::Bundler, compiler::Compiler, content::Content, locket::Locket};
+use crate::{
+    bundler::Bundler, compiler::Compiler, content::Content, loader::EMPTY_MODULE, locket::Locket,
+};
+
+/// Specifier and the exact version of the package it resolved to
+type ExternalKey = (String, Option<String>);
 
 #[derive(Default)]
 pub struct Externals {
-    dependencies: Locket<HashMap<String, Content>>,
+    dependencies: Locket<HashMap<ExternalKey, Content>>,
     bundler: Bundler,
 }
 
 impl Externals {
     pub fn get_or_bundle(&self, compiler: &Compiler, name: &str) -> anyhow::Result<Content> {
-        if let Some(found) = self.dependencies.read().get(name) {
+        let package = compiler.resolve(name)?;
+
+        // Upgrading a package bundles it again
+        let key = (name.to_string(), compiler.package_version(&package));
+
+        if let Some(found) = self.dependencies.read().get(&key) {
             return Ok(found.clone());
         }
 
-        log::debug!("bundle {}", name);
-
-        let bundle = self.bundler.bundle(compiler, name)?;
+        log::debug!("bundle {} ({:?})", name, key.1);
 
-        let content = Content::new(bundle.to_bytes(false)?);
+        let content = if package.entry.empty {
+            Content::new(EMPTY_MODULE.as_bytes().to_vec())
+        } else {
+            let bundle = self.bundler.bundle_package(compiler, package)?;
+            Content::new(bundle.to_bytes(false)?)
+        };
 
-        self.dependencies
-            .write()
-            .insert(name.to_string(), content.clone());
+        self.dependencies.write().insert(key, content.clone());
 
         Ok(content)
     }
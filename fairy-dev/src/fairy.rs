@@ -1,29 +1,60 @@
+use fairy_core::Explanation;
 use relative_path::RelativePath;
+use swc_common::sync::Lrc;
 
 use crate::{
     compiler::Compiler,
-    content_loader::{AssetLoader, ContentLoaderBox, Payload, ScriptLoader},
+    content_loader::{AssetLoader, ContentLoaderBox, Payload, PluginLoader, ScriptLoader},
+    resolver::Resolver,
     Error,
 };
 
 pub struct FairyDev {
     pub loaders: Vec<ContentLoaderBox>,
+    resolver: Lrc<Resolver>,
 }
 
 impl FairyDev {
     pub fn new(compiler: Compiler) -> FairyDev {
         let root = compiler.root().to_path_buf();
+        let resolver = compiler.resolver().clone();
+        let fs = compiler.file_system().clone();
+
+        let file_loaders = compiler.loaders().to_vec();
 
         let loaders = vec![
-            Box::new(ScriptLoader::new(compiler)) as ContentLoaderBox,
-            Box::new(AssetLoader::new(root)),
+            Box::new(PluginLoader::new(root.clone(), fs.clone(), file_loaders)) as ContentLoaderBox,
+            Box::new(ScriptLoader::new(compiler)),
+            Box::new(AssetLoader::new(root, fs)),
         ];
 
-        FairyDev { loaders }
+        FairyDev { loaders, resolver }
     }
 }
 
 impl FairyDev {
+    /// Log problems with the packages installed in node_modules
+    pub fn check_install(&self) {
+        let report = self.resolver.check_install();
+
+        for mismatch in report.mismatches {
+            log::warn!("{}", mismatch);
+        }
+
+        for duplicate in report.duplicates {
+            log::warn!("{}", duplicate);
+        }
+
+        for warning in report.peer_warnings {
+            log::warn!("{}", warning);
+        }
+    }
+
+    /// How `specifier` is resolved when imported from `importer`
+    pub fn explain(&self, importer: impl AsRef<RelativePath>, specifier: &str) -> Explanation {
+        self.resolver.explain(importer.as_ref(), specifier)
+    }
+
     pub fn resolve(&self, path: impl AsRef<RelativePath>) -> Result<Payload, Error> {
         let path = path.as_ref();
 
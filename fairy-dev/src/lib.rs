@@ -19,11 +19,21 @@ pub use self::{
     error::*,
 };
 
+use fairy_core::{FileSystem, OsFileSystem};
+use std::sync::Arc;
 use swc_atoms::JsWord;
 use swc_common::sync::Lrc;
 use swc_ecma_ast::{Expr, Lit};
 
 pub fn create_resolver(config: fairy_core::Config) -> anyhow::Result<FairyDev> {
+    create_resolver_with_fs(config, Arc::new(OsFileSystem))
+}
+
+/// Like `create_resolver`, reading sources through `fs`
+pub fn create_resolver_with_fs(
+    config: fairy_core::Config,
+    fs: Arc<dyn FileSystem>,
+) -> anyhow::Result<FairyDev> {
     let env = config
         .env
         .into_iter()
@@ -32,7 +42,13 @@ pub fn create_resolver(config: fairy_core::Config) -> anyhow::Result<FairyDev> {
 
     let env = Lrc::new(env);
 
-    let compiler = Compiler::new(config.root.clone(), env);
+    let compiler = Compiler::new(config.root.clone(), env, config.resolve, fs, config.plugins);
+
+    let dev = FairyDev::new(compiler);
+
+    if config.check_install {
+        dev.check_install();
+    }
 
-    Ok(FairyDev::new(compiler))
+    Ok(dev)
 }
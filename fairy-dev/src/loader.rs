@@ -1,6 +1,11 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use anyhow::Error;
+use fairy_core::{FileLoader as FilePlugin, FileSystem};
+use pathdiff::diff_paths;
 use relative_path::RelativePathBuf;
 use swc_atoms::JsWord;
 use swc_bundler::{Load, ModuleData};
@@ -22,12 +27,25 @@ use swc_ecma_visit::Fold;
 
 pub static NODE_MODULES_PREFIX: &'static str = "/node_modules/.fairy/";
 
+/// Sources of workspace packages outside the project root
+pub static WORKSPACE_PREFIX: &'static str = "/node_modules/.fairy-workspace/";
+
+/// Project files turned into modules by the configured `FileLoader`s
+pub static FILE_LOADER_PREFIX: &'static str = "/node_modules/.fairy-loader/";
+
+pub static EMPTY_MODULE_PREFIX: &'static str = "fairy:empty:";
+
+pub static EMPTY_MODULE: &'static str = "export default {};";
+
 #[derive(Clone)]
 pub struct Loader {
     pub cm: Lrc<SourceMap>,
     pub env: Lrc<AHashMap<JsWord, Expr>>,
     pub globals: Lrc<AHashMap<JsWord, Expr>>,
     pub handler: Lrc<Handler>,
+    root: PathBuf,
+    fs: Arc<dyn FileSystem>,
+    plugins: Vec<Arc<dyn FilePlugin>>,
 }
 
 impl Loader {
@@ -35,20 +53,56 @@ impl Loader {
         cm: Lrc<SourceMap>,
         env: Lrc<AHashMap<JsWord, Expr>>,
         handler: Lrc<Handler>,
+        root: PathBuf,
+        fs: Arc<dyn FileSystem>,
+        plugins: Vec<Arc<dyn FilePlugin>>,
     ) -> Loader {
         Loader {
             cm,
             env,
             globals: Default::default(),
             handler,
+            root,
+            fs,
+            plugins,
         }
     }
+
+    /// The module a `FileLoader` makes of the file at `path`, when one
+    /// handles it (eg. a stylesheet imported by a package)
+    fn load_plugin(&self, path: &Path) -> Result<Option<String>, Error> {
+        let rel_path = match diff_paths(path, &self.root)
+            .and_then(|path| RelativePathBuf::from_path(path).ok())
+        {
+            Some(rel_path) => rel_path,
+            None => return Ok(None),
+        };
+
+        let plugin = match self.plugins.iter().find(|plugin| plugin.matches(&rel_path)) {
+            Some(plugin) => plugin,
+            None => return Ok(None),
+        };
+
+        Ok(Some(plugin.load(&rel_path, self.fs.read(path)?)?))
+    }
 }
 
 impl Load for Loader {
     fn load(&self, f: &FileName) -> Result<ModuleData, Error> {
         let fm = match f {
-            FileName::Real(path) => self.cm.load_file(path)?,
+            FileName::Real(path) => match self.load_plugin(path)? {
+                Some(module) => self.cm.new_source_file(f.clone(), module),
+                // Packages requiring their JSON files
+                None if path.extension().map(|ext| ext == "json").unwrap_or(false) => {
+                    let json = self.fs.read_to_string(path)?;
+                    self.cm
+                        .new_source_file(f.clone(), format!("export default {};", json))
+                }
+                None => self.cm.load_file(path)?,
+            },
+            FileName::Custom(name) if name.starts_with(EMPTY_MODULE_PREFIX) => {
+                self.cm.new_source_file(f.clone(), EMPTY_MODULE.to_string())
+            }
             m => unreachable!("{:?}", m),
         };
 
@@ -108,6 +162,7 @@ impl Load for Loader {
 #[derive(Debug, Clone)]
 pub struct FileLoader {
     virtual_path: PathBuf,
+    fs: Arc<dyn FileSystem>,
 }
 
 impl FileLoader {
@@ -117,22 +172,22 @@ impl FileLoader {
 }
 
 impl FileLoader {
-    pub fn new(root: PathBuf) -> FileLoader {
+    pub fn new(root: PathBuf, fs: Arc<dyn FileSystem>) -> FileLoader {
         let vp = RelativePathBuf::from(NODE_MODULES_PREFIX);
 
         let virtual_path = vp.to_path(&root);
 
-        FileLoader { virtual_path }
+        FileLoader { virtual_path, fs }
     }
 }
 
 impl SwcFileLoader for FileLoader {
     fn file_exists(&self, path: &std::path::Path) -> bool {
-        todo!("exists {:?}", path);
+        self.fs.is_file(path)
     }
 
     fn abs_path(&self, path: &std::path::Path) -> Option<PathBuf> {
-        todo!("abs {:?}", path);
+        self.fs.canonicalize(path).ok()
     }
 
     fn read_file(&self, path: &std::path::Path) -> std::io::Result<String> {
@@ -150,6 +205,6 @@ export {{ cjs as default }};
             ));
         }
 
-        std::fs::read_to_string(path)
+        self.fs.read_to_string(path)
     }
 }
@@ -1,27 +1,77 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use anyhow::bail;
-use fairy_core::{ImportHint, Package, TargetEnv};
+use fairy_core::{
+    Explanation, FileSystem, ImportHint, InstallReport, Package, ResolveError, ResolveOptions,
+    TargetEnv, Workspace,
+};
 use pathdiff::diff_paths;
-use relative_path::RelativePathBuf;
+use relative_path::{RelativePath, RelativePathBuf};
 use swc_bundler::Resolve;
 use swc_common::FileName;
 
+use crate::loader::EMPTY_MODULE_PREFIX;
+
 pub struct Resolver {
     i: fairy_core::Resolver,
 }
 
 impl Resolver {
-    pub fn new(root: PathBuf) -> Resolver {
+    pub fn new(root: PathBuf, options: ResolveOptions, fs: Arc<dyn FileSystem>) -> Resolver {
         Resolver {
-            i: fairy_core::Resolver::new(root),
+            i: fairy_core::Resolver::with_file_system(root, options, fs),
         }
     }
 
-    pub fn resolve_external(&self, name: &str) -> Option<Package> {
+    pub fn resolve_external(&self, name: &str) -> Result<Package, ResolveError> {
         self.i
             .resolve("./main.js", name, ImportHint::Import, TargetEnv::Browser)
     }
+
+    pub fn resolve_from(&self, base: &RelativePath, name: &str) -> Result<Package, ResolveError> {
+        self.i
+            .resolve(base, name, ImportHint::Import, TargetEnv::Browser)
+    }
+
+    pub fn explain(&self, base: &RelativePath, name: &str) -> Explanation {
+        self.i
+            .explain(base, name, ImportHint::Import, TargetEnv::Browser)
+    }
+
+    pub fn root(&self) -> &Path {
+        self.i.root()
+    }
+
+    pub fn options(&self) -> &ResolveOptions {
+        self.i.options()
+    }
+
+    pub fn extensions(&self) -> &[String] {
+        self.i.extensions()
+    }
+
+    pub fn file_system(&self) -> &Arc<dyn FileSystem> {
+        self.i.file_system()
+    }
+
+    pub fn workspace(&self) -> Option<Arc<Workspace>> {
+        self.i.workspace()
+    }
+
+    pub fn package_version(&self, package: &Package) -> Option<String> {
+        self.i.package_version(package)
+    }
+
+    pub fn check_install(&self) -> InstallReport {
+        self.i.check_install()
+    }
+
+    pub fn invalidate(&self, path: &Path) {
+        self.i.invalidate(path)
+    }
 }
 
 impl Resolve for Resolver {
@@ -46,17 +96,17 @@ impl Resolve for Resolver {
             module_specifier,
             ImportHint::Import,
             TargetEnv::Browser,
-        );
-
-        match package {
-            Some(package) => {
-                let file_name = FileName::Real(package.entry.path.to_logical_path(&package.root));
+        )?;
 
-                Ok(file_name)
-            }
-            None => {
-                bail!("module not found: {} from base: {}", module_specifier, path)
-            }
+        if package.entry.empty {
+            return Ok(FileName::Custom(format!(
+                "{}{}",
+                EMPTY_MODULE_PREFIX, module_specifier
+            )));
         }
+
+        Ok(FileName::Real(
+            package.entry.path.to_logical_path(&package.root),
+        ))
     }
 }
@@ -2,6 +2,8 @@ use relative_path::RelativePath;
 use swc_common::DUMMY_SP;
 use swc_ecma_ast::*;
 
+use crate::loader::FILE_LOADER_PREFIX;
+
 use super::ImportTransformer;
 
 macro_rules! var_decl {
@@ -48,6 +50,11 @@ impl ImportTransformer for AssetsTransform {
             return Some(import);
         }
 
+        // Already pointed at the module of a `FileLoader`
+        if import.src.value.starts_with(FILE_LOADER_PREFIX) {
+            return Some(import);
+        }
+
         if import.specifiers.len() > 1 {
             return Some(import);
         }
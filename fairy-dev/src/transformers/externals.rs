@@ -1,12 +1,13 @@
 use inflector::Inflector;
 use relative_path::RelativePath;
 use swc_atoms::{js_word, JsWord};
-use swc_common::DUMMY_SP;
+use swc_common::{sync::Lrc, DUMMY_SP};
 use swc_ecma_ast::*;
 
-use crate::loader::NODE_MODULES_PREFIX;
-
-pub static EXTENSIONS: &'static [&'static str] = &["ts", "tsx", "js", "jsx", "mjs"];
+use crate::{
+    loader::{NODE_MODULES_PREFIX, WORKSPACE_PREFIX},
+    resolver::Resolver,
+};
 
 use super::import::ImportTransformer;
 
@@ -32,12 +33,39 @@ macro_rules! var_decl {
     };
 }
 
-pub struct Externals {}
+pub struct Externals {
+    resolver: Lrc<Resolver>,
+}
 
 impl Externals {
-    pub fn new() -> Externals {
-        Externals {}
+    pub fn new(resolver: Lrc<Resolver>) -> Externals {
+        Externals { resolver }
     }
+
+    fn resolve_local_import(&self, file: &RelativePath, specifier: &str) -> Option<String> {
+        let package = self.resolver.resolve_from(file, specifier).ok()?;
+        if package.entry.empty || package.entry.builtin {
+            return None;
+        }
+
+        let path = package.entry.path.to_logical_path(&package.root);
+
+        if let Ok(rel_path) = path.strip_prefix(self.resolver.root()) {
+            let rel_path = RelativePath::from_path(rel_path).ok()?;
+            if !rel_path.components().any(|m| m.as_str() == "node_modules") {
+                return Some(format!("/{}", rel_path));
+            }
+        }
+
+        // Workspace packages are first-party code, even when they live
+        // outside the project root
+        let workspace = self.resolver.workspace()?;
+        let (name, root) = workspace.package_at(&path)?;
+        let rel_path = RelativePath::from_path(path.strip_prefix(root).ok()?).ok()?;
+
+        Some(format!("{}{}/{}", WORKSPACE_PREFIX, name, rel_path))
+    }
+
     // pub fn resolve(&self, path: &RelativePath) -> bool {
     //     if let Some(ext) = path.extension() {
     //         if !EXTENSIONS.contains(&ext) {
@@ -62,7 +90,7 @@ impl Externals {
 impl ImportTransformer for Externals {
     fn rewrite_import(
         &self,
-        _file: &RelativePath,
+        file: &RelativePath,
         mut import: ImportDecl,
         items: &mut Vec<ModuleItem>,
     ) -> Option<ImportDecl> {
@@ -70,6 +98,14 @@ impl ImportTransformer for Externals {
             return Some(import);
         }
 
+        // Specifiers mapped onto our own sources (package imports, tsconfig paths)
+        // are served as is
+        if let Some(path) = self.resolve_local_import(file, &import.src.value) {
+            let path: JsWord = path.into();
+            import.src = Box::new(path.into());
+            return Some(import);
+        }
+
         let node = import.clone();
 
         let path: JsWord = format!("{}{}", NODE_MODULES_PREFIX, import.src.value).into();
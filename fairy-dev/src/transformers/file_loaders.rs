@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use fairy_core::FileLoader;
+use relative_path::RelativePath;
+use swc_atoms::JsWord;
+use swc_common::sync::Lrc;
+use swc_ecma_ast::*;
+
+use crate::{loader::FILE_LOADER_PREFIX, resolver::Resolver};
+
+use super::ImportTransformer;
+
+/// Points imports of files handled by a `FileLoader` at the module the
+/// loader makes of them. Loaders are matched against the resolved file, so
+/// package (`lib/dist/style.css`) and aliased imports are handled as well
+pub struct FileLoaderTransform {
+    resolver: Lrc<Resolver>,
+    loaders: Vec<Arc<dyn FileLoader>>,
+}
+
+impl FileLoaderTransform {
+    pub fn new(resolver: Lrc<Resolver>, loaders: Vec<Arc<dyn FileLoader>>) -> FileLoaderTransform {
+        FileLoaderTransform { resolver, loaders }
+    }
+}
+
+impl ImportTransformer for FileLoaderTransform {
+    fn rewrite_import(
+        &self,
+        file: &RelativePath,
+        mut import: ImportDecl,
+        _items: &mut Vec<ModuleItem>,
+    ) -> Option<ImportDecl> {
+        if self.loaders.is_empty() {
+            return Some(import);
+        }
+
+        let package = match self.resolver.resolve_from(file, &import.src.value) {
+            Ok(package) if !package.entry.empty && !package.entry.builtin => package,
+            _ => return Some(import),
+        };
+
+        let path = package.entry.path.to_logical_path(&package.root);
+
+        let src = match path
+            .strip_prefix(self.resolver.root())
+            .ok()
+            .and_then(|path| RelativePath::from_path(path).ok())
+        {
+            Some(src) => src.normalize(),
+            None => {
+                log::debug!("file outside the project root: {:?}", path);
+                return Some(import);
+            }
+        };
+
+        if !self.loaders.iter().any(|loader| loader.matches(&src)) {
+            return Some(import);
+        }
+
+        let path: JsWord = format!("{}{}", FILE_LOADER_PREFIX, src).into();
+        import.src = Box::new(path.into());
+
+        Some(import)
+    }
+}
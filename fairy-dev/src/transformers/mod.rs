@@ -1,11 +1,13 @@
 mod assets;
 mod externals;
+mod file_loaders;
 mod import;
 mod require;
 
 pub use self::{
     assets::AssetsTransform,
-    externals::{Externals, EXTENSIONS},
+    externals::Externals,
+    file_loaders::FileLoaderTransform,
     import::{ImportTransform, ImportTransformer, ImportTransportFold},
     require::RequireTransform,
 };
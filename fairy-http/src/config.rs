@@ -1,7 +1,7 @@
 use dale_http::error::Error;
-use fairy_core::Environ;
+use fairy_core::{Config, Environ, FileLoader, ResolveOptions};
 use relative_path::RelativePathBuf;
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 pub struct RenderRequest {
     pub scripts: Vec<String>,
@@ -56,6 +56,9 @@ pub struct Options {
     pub env: Environ,
     pub public: RelativePathBuf,
     pub template: TemplateBox,
+    pub resolve: ResolveOptions,
+    pub plugins: Vec<Arc<dyn FileLoader>>,
+    pub check_install: bool,
 }
 
 impl Options {
@@ -70,6 +73,9 @@ pub struct OptionsBuilder {
     env: Environ,
     public: Option<RelativePathBuf>,
     template: Option<TemplateBox>,
+    resolve: ResolveOptions,
+    plugins: Vec<Arc<dyn FileLoader>>,
+    check_install: bool,
 }
 
 impl OptionsBuilder {
@@ -80,6 +86,9 @@ impl OptionsBuilder {
             env: Environ::default(),
             template: None,
             public: None,
+            resolve: ResolveOptions::default(),
+            plugins: Vec::default(),
+            check_install: false,
         }
     }
 
@@ -102,11 +111,64 @@ impl OptionsBuilder {
         self
     }
 
+    /// Add variables, eg. loaded with `fairy_core::load_env`
+    pub fn env(mut self, env: Environ) -> Self {
+        self.env.extend(env);
+        self
+    }
+
     pub fn public(mut self, public: impl Into<RelativePathBuf>) -> Self {
         self.public = Some(public.into());
         self
     }
 
+    pub fn resolve(mut self, resolve: ResolveOptions) -> Self {
+        self.resolve = resolve;
+        self
+    }
+
+    pub fn alias(mut self, name: impl ToString, target: impl ToString) -> Self {
+        self.resolve
+            .alias
+            .insert(name.to_string(), target.to_string());
+        self
+    }
+
+    pub fn dedupe(mut self, name: impl ToString) -> Self {
+        self.resolve.dedupe.push(name.to_string());
+        self
+    }
+
+    /// Load imports of non-script files with `loader`, eg. `fairy_core::CssLoader`
+    pub fn plugin(mut self, loader: impl FileLoader + 'static) -> Self {
+        self.plugins.push(Arc::new(loader));
+        self
+    }
+
+    pub fn plugins(mut self, plugins: Vec<Arc<dyn FileLoader>>) -> Self {
+        self.plugins.extend(plugins);
+        self
+    }
+
+    /// Apply the settings of a `fairy_core::ConfigFile`. The root given to
+    /// the builder is kept
+    pub fn config(mut self, config: Config) -> Self {
+        if !config.entry.as_str().is_empty() {
+            self.entry = Some(config.entry);
+        }
+        self.env.extend(config.env);
+        self.resolve = config.resolve;
+        self.plugins.extend(config.plugins);
+        self.check_install |= config.check_install;
+        self
+    }
+
+    /// Check the packages in node_modules on start
+    pub fn check_install(mut self, check: bool) -> Self {
+        self.check_install = check;
+        self
+    }
+
     pub fn build(self) -> Result<Options, std::convert::Infallible> {
         let template = self.template.unwrap();
         let entry = self.entry.unwrap();
@@ -118,6 +180,9 @@ impl OptionsBuilder {
             entry,
             env: self.env,
             template,
+            resolve: self.resolve,
+            plugins: self.plugins,
+            check_install: self.check_install,
             public: self
                 .public
                 .unwrap_or_else(|| RelativePathBuf::from("./public")),
@@ -0,0 +1,59 @@
+use dale::Service;
+use dale_http::{
+    error::Error,
+    prelude::{Set, With},
+    Body, Outcome, Request, Response,
+};
+use fairy_dev::FairyDev;
+use std::{collections::HashMap, future::Future, sync::Arc};
+
+pub static EXPLAIN_PATH: &'static str = "/__fairy/explain";
+
+/// Debug endpoint showing how a specifier is resolved, eg.
+/// `/__fairy/explain?specifier=react&importer=src/main.tsx`
+pub fn explain<B>(
+    dev: Arc<FairyDev>,
+) -> impl Service<Request<B>, Future = impl Future + Send, Output = Outcome<B>> + Clone
+where
+    B: Body + Send + 'static,
+{
+    move |req: Request<B>| {
+        let dev = dev.clone();
+
+        async move {
+            if req.uri().path() != EXPLAIN_PATH {
+                return Outcome::Next(req);
+            }
+
+            let query = form_urlencoded::parse(req.uri().query().unwrap_or_default().as_bytes())
+                .into_owned()
+                .collect::<HashMap<_, _>>();
+
+            let specifier = match query.get("specifier") {
+                Some(specifier) => specifier,
+                None => {
+                    return Outcome::Failure(Error::new(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "missing specifier",
+                    )))
+                }
+            };
+
+            let importer = query
+                .get("importer")
+                .map(|importer| importer.as_str())
+                .unwrap_or("./main.js");
+
+            let explanation = dev.explain(importer, specifier);
+
+            let json = match serde_json::to_vec(&explanation) {
+                Ok(ret) => ret,
+                Err(err) => return Outcome::Failure(Error::new(err)),
+            };
+
+            let resp = Response::<B>::with(json).set(dale_http::headers::ContentType::json());
+
+            Outcome::Success(resp)
+        }
+    }
+}
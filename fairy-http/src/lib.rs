@@ -4,10 +4,11 @@ use dale::{Service, ServiceExt};
 use dale_http::{prelude::Modifier, Body, Outcome, Request, Response};
 
 mod config;
+mod explain;
 mod index;
 mod statics;
 
-pub use config::*;
+pub use self::{config::*, explain::EXPLAIN_PATH};
 use dale_runtime::executor::Executor;
 
 pub fn create_routes<B, E>(
@@ -18,8 +19,12 @@ where
     E: Executor + 'static,
     E::Error: std::error::Error + Send + Sync + 'static,
 {
+    let dev = Arc::new(statics::create_dev(&cfg));
+
     dale_http::fs::dir(cfg.public.to_path(&cfg.root))
-        .or(statics::statics::<B, E>(cfg.clone()))
+        .or(explain::explain::<B>(dev.clone()))
+        .unify()
+        .or(statics::statics::<B, E>(dev))
         .unify()
         .or(index::index(cfg))
         .unify()
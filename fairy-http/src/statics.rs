@@ -6,31 +6,33 @@ use dale_http::{
 };
 use dale_runtime::executor::Executor;
 use fairy_core::Config;
-use fairy_dev::create_resolver;
+use fairy_dev::{create_resolver, FairyDev};
 use futures_channel::oneshot;
 use std::{future::Future, sync::Arc};
 
 use crate::Options;
 
-pub fn statics<B, E>(
-    cfg: Arc<Options>,
-) -> impl Service<Request<B>, Future = impl Future + Send, Output = Outcome<B>> + Clone
-where
-    B: Body + Send + 'static,
-    E: Executor,
-    E::Error: std::error::Error + Send + Sync + 'static,
-{
+pub fn create_dev(cfg: &Options) -> FairyDev {
     let config = Config {
         root: cfg.root.clone(),
         entry: cfg.entry.clone(),
         env: cfg.env.clone(),
-        plugins: vec![],
+        plugins: cfg.plugins.clone(),
+        resolve: cfg.resolve.clone(),
+        check_install: cfg.check_install,
     };
 
-    let resolver = create_resolver(config).expect("create resolver");
-
-    let resolver = Arc::new(resolver);
+    create_resolver(config).expect("create resolver")
+}
 
+pub fn statics<B, E>(
+    resolver: Arc<FairyDev>,
+) -> impl Service<Request<B>, Future = impl Future + Send, Output = Outcome<B>> + Clone
+where
+    B: Body + Send + 'static,
+    E: Executor,
+    E::Error: std::error::Error + Send + Sync + 'static,
+{
     let tp = threadpool::builder().build();
 
     move |req: Request<B>| {
@@ -60,9 +62,13 @@ where
                 Err(err) => return Outcome::Failure(Error::new(err)),
             };
 
-            let resp = Response::<B>::with(bytes.content.to_bytes())
+            let mut resp = Response::<B>::with(bytes.content.to_bytes())
                 .set(dale_http::headers::ContentType::from(bytes.mime));
 
+            if let Some(value) = bytes.source_map.and_then(|url| url.parse().ok()) {
+                resp.headers_mut().insert("sourcemap", value);
+            }
+
             Outcome::Success(resp)
         }
     }